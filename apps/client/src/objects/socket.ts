@@ -1,20 +1,82 @@
-import { Player } from '@super-swash-bros/api';
+import {
+  ConnectTo,
+  ErrorInfo,
+  IceConfig,
+  GameMode,
+  Player,
+  PlayerLeft,
+  PlayerStatus,
+  QueueStatus,
+  Ready,
+  RoomSettings,
+  RoomState,
+  StateInfo,
+  You,
+} from '@super-swash-bros/api';
 
 type Response =
-  | ({ cmd: 'you' } & Player)
+  | ({ cmd: 'you' } & You)
   | ({ cmd: 'player' } & Player)
-  | { cmd: 'room'; code?: string; players: (Player | null)[] }
-  | { cmd: 'offer'; offer: RTCSessionDescriptionInit }
-  | { cmd: 'answer'; offer: RTCSessionDescriptionInit }
-  | { cmd: 'ice'; candidate: RTCIceCandidate };
+  | ({ cmd: 'left' } & PlayerLeft)
+  | ({ cmd: 'disconnected' } & PlayerStatus)
+  | ({ cmd: 'reconnected' } & PlayerStatus)
+  | ({ cmd: 'host' } & PlayerStatus)
+  | ({ cmd: 'ready' } & Ready)
+  | ({ cmd: 'state' } & StateInfo)
+  | ({ cmd: 'queue' } & QueueStatus)
+  | ({ cmd: 'connect_to' } & ConnectTo)
+  | ({ cmd: 'ice_config' } & IceConfig)
+  | {
+      cmd: 'room';
+      code?: string;
+      players: (Player | null)[];
+      host?: string;
+      state: RoomState;
+      settings: RoomSettings;
+      spectators: Player[];
+      spectator_mesh: boolean;
+    }
+  | { cmd: 'offer'; offer: RTCSessionDescriptionInit; from?: string }
+  | { cmd: 'answer'; offer: RTCSessionDescriptionInit; from?: string }
+  | { cmd: 'ice'; candidate: RTCIceCandidate; from?: string }
+  | ({ cmd: 'error' } & ErrorInfo);
 
 type Request =
-  | { cmd: 'create' }
+  | {
+      cmd: 'create';
+      max_players?: number;
+      settings?: Partial<RoomSettings>;
+    }
   | { cmd: 'choice'; c: number }
   | { cmd: 'join'; code: string }
-  | { cmd: 'offer'; offer: RTCSessionDescriptionInit }
-  | { cmd: 'answer'; offer: RTCSessionDescriptionInit }
-  | { cmd: 'ice'; candidate: RTCIceCandidate };
+  | { cmd: 'quickmatch' }
+  | {
+      cmd: 'queue';
+      party_size?: number;
+      mode?: GameMode;
+      rating?: number;
+    }
+  | { cmd: 'unqueue' }
+  | { cmd: 'leave' }
+  | { cmd: 'resume'; token: string }
+  | { cmd: 'seat'; i: number }
+  | { cmd: 'host'; id: string }
+  | { cmd: 'kick'; id: string; reason?: string }
+  | { cmd: 'ban'; id: string; reason?: string }
+  | { cmd: 'ready'; ready: boolean }
+  | { cmd: 'start' }
+  | { cmd: 'end' }
+  | { cmd: 'lobby' }
+  | { cmd: 'spectators'; mesh: boolean }
+  | { cmd: 'settings'; settings: Partial<RoomSettings> }
+  | { cmd: 'offer'; offer: RTCSessionDescriptionInit; to?: string }
+  | { cmd: 'answer'; offer: RTCSessionDescriptionInit; to?: string }
+  | { cmd: 'ice'; candidate: RTCIceCandidate; to?: string };
+
+/**
+ * the target slot of a relayed frame that sends it to the whole room
+ */
+const BROADCAST_SLOT = 0xff;
 
 type Handler = {
   [K in Response['cmd']]?: (msg: Extract<Response, { cmd: K }>) => void;
@@ -23,16 +85,23 @@ type Handler = {
 class LinkCable {
   socket?: WebSocket;
   handlers: Handler = {};
+  frameHandler?: (frame: ArrayBuffer) => void;
   connect(url: string): Promise<LinkCable> {
     if (this.socket) return Promise.resolve(this);
     return new Promise((resolve, reject) => {
       this.socket = new WebSocket(url);
+      this.socket.binaryType = 'arraybuffer';
       this.socket.addEventListener('open', () => {
         resolve(this);
       });
 
       // Listen for messages
       this.socket.addEventListener('message', (event) => {
+        // game frames relayed by the server
+        if (event.data instanceof ArrayBuffer) {
+          this.frameHandler?.(event.data);
+          return;
+        }
         try {
           const msg = JSON.parse(event.data) as Response;
           // @ts-expect-error TS can't strongly infer this type but we know it is allowed
@@ -69,6 +138,29 @@ class LinkCable {
     return this;
   }
 
+  /**
+   * handle game frames relayed by the server
+   */
+  onFrame(func: (frame: ArrayBuffer) => void): LinkCable {
+    this.frameHandler = func;
+    return this;
+  }
+
+  /**
+   * send a game frame through the server, for when the data channel is not available
+   * @param frame the frame to send. It must start with the sender's seat, or the server drops it
+   * @param target the slot of the player to send it to, the whole room by default
+   */
+  relay(frame: ArrayBuffer, target = BROADCAST_SLOT) {
+    if (this.socket && this.socket.readyState === WebSocket.OPEN) {
+      // the server reads the target from the first byte and strips it off
+      const packet = new Uint8Array(frame.byteLength + 1);
+      packet[0] = target;
+      packet.set(new Uint8Array(frame), 1);
+      this.socket.send(packet.buffer);
+    }
+  }
+
   emit(cmd: Request) {
     if (this.socket && this.socket.OPEN) {
       this.socket.send(JSON.stringify(cmd));
@@ -76,4 +168,4 @@ class LinkCable {
   }
 }
 
-export { LinkCable };
+export { LinkCable, BROADCAST_SLOT };
@@ -4,7 +4,10 @@ import { Player, api } from '@super-swash-bros/api';
 import { isDefined } from '@super-swash-bros/utils';
 import World from './worldgen';
 
-const configuration = {
+/**
+ * used until link-cable sends its own ICE servers
+ */
+const defaultConfiguration: RTCConfiguration = {
   iceServers: [
     {
       urls: 'stun:stun.l.google.com:19302',
@@ -12,14 +15,21 @@ const configuration = {
   ],
 };
 
+/**
+ * length of the room codes link-cable generates (its `ROOM_CODE_LENGTH`)
+ */
+const CODE_LENGTH = 6;
+
 class Lobby extends Scene {
   sessionId?: string;
+  hostId?: string;
   players: (Player | null)[] = [];
   hostText: Phaser.GameObjects.Text;
   playBtn?: Phaser.GameObjects.Image;
   slots: Phaser.GameObjects.Text[];
   socket?: LinkCable;
   rtc?: RTCPeerConnection;
+  rtcConfiguration = defaultConfiguration;
   cable?: RTCDataChannel;
   constructor() {
     super('Lobby');
@@ -37,12 +47,12 @@ class Lobby extends Scene {
 
     const input = document.createElement('input');
     input.type = 'text';
-    input.maxLength = 4;
+    input.maxLength = CODE_LENGTH;
     // input.style.opacity = '0';
     input.addEventListener('input', (evt: InputEvent) => {
       const value = (evt.currentTarget as HTMLInputElement).value;
       text.setText(value.toUpperCase());
-      value.length === 4 ? go.setInteractive() : go.disableInteractive();
+      value.length === CODE_LENGTH ? go.setInteractive() : go.disableInteractive();
     });
     document.body.appendChild(input);
 
@@ -106,8 +116,7 @@ class Lobby extends Scene {
      */
     this.input.on('pointerdown', () => {
       const c = Math.floor(Math.random() * 10);
-      const idx = this.players.findIndex((p) => p.id === this.sessionId);
-      if (idx > -1) this.players[idx].c = c;
+      // the server echoes the choice back once it is accepted
       this.socket?.emit({
         cmd: 'choice',
         c,
@@ -158,7 +167,7 @@ class Lobby extends Scene {
     /**
      * create the host RTCPeerConnection
      */
-    this.rtc = new RTCPeerConnection(configuration);
+    this.rtc = new RTCPeerConnection(this.rtcConfiguration);
     const cable = this.rtc.createDataChannel('playerData', {
       ordered: false,
     });
@@ -191,7 +200,7 @@ class Lobby extends Scene {
     queuedIceCandidates: RTCIceCandidate[]
   ) {
     if (!isDefined(this.rtc)) {
-      this.rtc = new RTCPeerConnection(configuration);
+      this.rtc = new RTCPeerConnection(this.rtcConfiguration);
       this.rtc.onicecandidate = (event) => {
         if (isDefined(event.candidate)) {
           console.log('generated', event.candidate.usernameFragment);
@@ -239,12 +248,16 @@ class Lobby extends Scene {
     const world = this.scene.manager.getScene('World') as World;
     world.rtc = this.rtc;
     world.cable = this.cable;
+    world.socket = this.socket;
     world.initPlayers(this.sessionId, this.players);
   }
 
   async connectCable() {
     const queuedIceCandidates: RTCIceCandidate[] = [];
     return await new LinkCable()
+      .on('ice_config', (msg) => {
+        this.rtcConfiguration = { iceServers: msg.ice_servers };
+      })
       .on('room', (msg) => {
         if (!msg.code) {
           //TODO handle room failed to create
@@ -252,23 +265,29 @@ class Lobby extends Scene {
         }
         this.hostText.setText(msg.code);
         this.players = msg.players;
+        this.setHost(msg.host);
+      })
+      .on('host', (msg) => {
+        this.setHost(msg.id);
       })
       .on('player', (msg) => {
         if (isDefined(msg.i)) {
           this.players[msg.i] = msg;
         }
         if (isDefined(msg.c)) {
-          const idx = this.players.findIndex((p) => p.id === msg.id);
+          const idx = this.players.findIndex((p) => p?.id === msg.id);
           if (idx > -1) this.players[idx].c = msg.c;
         }
       })
+      .on('left', (msg) => {
+        if (isDefined(msg.i)) {
+          this.players[msg.i] = null;
+          this.slots[msg.i]?.setText('');
+        }
+      })
       .on('you', (msg) => {
         this.sessionId = msg.id;
-        if (isDefined(msg.i) && msg.i === 0) {
-          this.playBtn.setVisible(true).setInteractive();
-        } else {
-          this.playBtn.setVisible(false).disableInteractive();
-        }
+        this.setHost(this.hostId);
       })
       .on('offer', (msg) => {
         this.handleDescription(msg.offer, queuedIceCandidates);
@@ -293,6 +312,17 @@ class Lobby extends Scene {
         return socket;
       });
   }
+  /**
+   * only the host of the room can start the game
+   */
+  setHost(id?: string) {
+    this.hostId = id;
+    if (isDefined(id) && id === this.sessionId) {
+      this.playBtn.setVisible(true).setInteractive();
+    } else {
+      this.playBtn.setVisible(false).disableInteractive();
+    }
+  }
   update() {
     this.slots.forEach((slot, index) => {
       if (isDefined(this.players[index])) {
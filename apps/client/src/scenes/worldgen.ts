@@ -2,10 +2,14 @@ import { Scene } from 'phaser';
 import Pirate from '../objects/player';
 import { isDefined } from '@super-swash-bros/utils';
 import { Player } from '@super-swash-bros/api';
+import { LinkCable } from '../objects/socket';
 
 class World extends Scene {
   rtc?: RTCPeerConnection;
   cable?: RTCDataChannel;
+  /** relays game frames through the server when the data channel is not open */
+  socket?: LinkCable;
+  /** indexed by seat, so empty seats leave gaps */
   pirates: Pirate[] = [];
   sessionId?: string;
   ctrlIndex: number = 0;
@@ -20,18 +24,18 @@ class World extends Scene {
   }
 
   initPlayers(sessionId: string, players: (Player | null)[]) {
-    for (const config of players) {
+    players.forEach((config, seat) => {
       if (isDefined(config)) {
-        const pirate = new Pirate(this, 100, 100);
-        this.pirates.push(pirate);
+        this.pirates[seat] = new Pirate(this, 100, 100);
       }
-    }
+    });
     this.ctrlIndex = players.findIndex((p) => p?.id === sessionId);
     if (isDefined(this.cable)) {
       this.cable.onmessage = (event) => {
         this.decode(event.data);
       };
     }
+    this.socket?.onFrame((frame) => this.decode(frame));
   }
   create() {
     this.cameras.main.setBackgroundColor('#08bb08');
@@ -128,23 +132,25 @@ class World extends Scene {
         this.pirates[this.ctrlIndex].paused
       )
         return;
-      if (isDefined(this.cable) && this.cable.readyState === 'open') {
-        if (isDefined(this.keys)) {
-          const {
-            left: { isDown: left },
-            right: { isDown: right },
-            up: { isDown: up },
-            down: { isDown: down },
-          } = this.keys;
-          const buffer = new Uint8Array(2);
-          buffer[0] = this.ctrlIndex;
-          //bitwise construct the state of the keys
-          buffer[1] =
-            (left ? 1 : 0) |
-            ((right ? 1 : 0) << 1) |
-            ((up ? 1 : 0) << 2) |
-            ((down ? 1 : 0) << 3);
+      if (isDefined(this.keys)) {
+        const {
+          left: { isDown: left },
+          right: { isDown: right },
+          up: { isDown: up },
+          down: { isDown: down },
+        } = this.keys;
+        const buffer = new Uint8Array(2);
+        buffer[0] = this.ctrlIndex;
+        //bitwise construct the state of the keys
+        buffer[1] =
+          (left ? 1 : 0) |
+          ((right ? 1 : 0) << 1) |
+          ((up ? 1 : 0) << 2) |
+          ((down ? 1 : 0) << 3);
+        if (isDefined(this.cable) && this.cable.readyState === 'open') {
           this.cable.send(buffer.buffer);
+        } else {
+          this.socket?.relay(buffer.buffer);
         }
       }
     };
@@ -168,7 +174,7 @@ class World extends Scene {
     if (up || down) {
       yacc = ((up ? -1 : 0) + (down ? 1 : 0)) * 300;
     }
-    this.pirates[buffer[0]].body.setAcceleration(xacc, yacc);
+    this.pirates[buffer[0]]?.body.setAcceleration(xacc, yacc);
   }
 
   update() {
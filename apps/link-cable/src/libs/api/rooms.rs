@@ -10,9 +10,51 @@ pub struct Announcement {
     message: String,
 }
 
+/// filters for the room listing
+#[derive(Deserialize)]
+pub struct RoomFilter {
+    /// only list public rooms
+    #[serde(default)]
+    public: bool,
+}
+
 #[get("")]
-async fn get_rooms(srv: web::Data<Addr<server::RoomServer>>) -> impl Responder {
-    HttpResponse::Ok().body("rooms response")
+async fn get_rooms(
+    srv: web::Data<Addr<server::RoomServer>>,
+    filter: web::Query<RoomFilter>,
+) -> impl Responder {
+    match srv
+        .send(server::ListRooms {
+            public_only: filter.public,
+        })
+        .await
+    {
+        Ok(rooms) => HttpResponse::Ok().json(rooms),
+        Err(e) => {
+            log::error!("failed to list rooms: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[get("/{code}")]
+async fn get_room(
+    srv: web::Data<Addr<server::RoomServer>>,
+    code: web::Path<String>,
+) -> impl Responder {
+    match srv
+        .send(server::GetRoom {
+            code: code.into_inner(),
+        })
+        .await
+    {
+        Ok(Some(room)) => HttpResponse::Ok().json(room),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            log::error!("failed to get room: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
 }
 
 #[post("/announce")]
@@ -27,5 +69,5 @@ async fn announce(
 }
 
 pub fn api(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_rooms).service(announce);
+    cfg.service(get_rooms).service(get_room).service(announce);
 }
@@ -0,0 +1,227 @@
+//! Server configuration. Every value has a sensible default and can be
+//! overridden through an environment variable at startup.
+
+use std::{env, str::FromStr, time::Duration};
+
+use crate::libs::socket::responses::{GameMode, RoomSettings};
+
+/// letters used for room codes. Codes get read aloud, so only one letter is
+/// kept from each group that sounds alike (b/c/d/e/g/p/t/v/z, a/j/k, m/n, f/s,
+/// q/u), and letters easily confused when handwritten (i, l, o) are left out.
+/// With so few letters codes need to be longer to leave enough of them free
+const DEFAULT_CODE_ALPHABET: &str = "ehkmrsuwxy";
+
+/// words that may never appear in a generated room code
+const DEFAULT_CODE_BLOCKLIST: &[&str] = &[
+    "anal", "anus", "arse", "butt", "cock", "coon", "crap", "cum", "cunt", "dick", "dyke", "fag",
+    "fuck", "gay", "jizz", "kike", "nazi", "nig", "piss", "poop", "porn", "pube", "rape", "sex",
+    "shit", "slut", "spic", "tit", "turd", "twat", "wank", "whor",
+];
+
+/// public STUN server clients use when none is configured
+const DEFAULT_STUN_URL: &str = "stun:stun.l.google.com:19302";
+
+/// tuning for the `RoomServer` actor
+#[derive(Clone, Debug)]
+pub struct RoomConfig {
+    /// whether the client address is taken from the `Forwarded` and
+    /// `X-Forwarded-For` headers. Only enable behind a proxy that sets them,
+    /// since clients can send them too
+    pub trust_proxy: bool,
+    /// how long an empty room is kept so that its players can come back to it.
+    /// A zero grace period removes rooms as soon as the last player leaves
+    pub empty_room_grace: Duration,
+    /// how often the server looks for stale rooms to remove
+    pub sweep_interval: Duration,
+    /// how long a player who lost connection keeps their seat, so that they can
+    /// resume their session. A zero window removes them immediately
+    pub resume_window: Duration,
+    /// how long the countdown before a match lasts
+    pub match_countdown: Duration,
+    /// whether players joining a room after its match started are let in as
+    /// spectators. Otherwise they are turned away
+    pub allow_spectators: bool,
+    /// number of playable characters. Valid choices are `0..roster_size`
+    pub roster_size: u8,
+    /// whether every player in a room must choose a different character
+    pub unique_characters: bool,
+    /// the longest match time limit a host may set
+    pub max_time_limit: Duration,
+    /// how far apart in rating queued players may be when they start waiting
+    pub queue_tolerance: u32,
+    /// how much the rating tolerance grows for every second a player waits
+    pub queue_widening: u32,
+    /// how often the matchmaker tries to form matches
+    pub queue_interval: Duration,
+    /// rating for queued players who do not provide one
+    pub default_rating: u32,
+    /// number of seats in a room when the creator does not ask for a size
+    pub default_max_players: usize,
+    /// the largest room a player may create
+    pub max_players_limit: usize,
+    /// number of characters in a room code
+    pub code_length: usize,
+    /// characters room codes are generated from
+    pub code_alphabet: Vec<char>,
+    /// words that generated room codes must not contain
+    pub code_blocklist: Vec<String>,
+    /// STUN servers handed to clients
+    pub stun_urls: Vec<String>,
+    /// TURN servers handed to clients, when a TURN secret is set
+    pub turn_urls: Vec<String>,
+    /// secret shared with the TURN servers for generating credentials
+    pub turn_secret: Option<String>,
+    /// how long generated TURN credentials are valid
+    pub turn_ttl: Duration,
+    /// how many requests a client may send per second
+    pub rate_limit_requests: u32,
+    /// how many WebRTC signaling messages a client may send per second, on top
+    /// of other requests. Joining a room sends an offer and ICE candidates to
+    /// every peer at once
+    pub rate_limit_signaling: u32,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        Self {
+            trust_proxy: false,
+            empty_room_grace: Duration::from_secs(30),
+            sweep_interval: Duration::from_secs(10),
+            resume_window: Duration::from_secs(30),
+            match_countdown: Duration::from_secs(3),
+            allow_spectators: true,
+            roster_size: 10,
+            unique_characters: false,
+            max_time_limit: Duration::from_secs(600),
+            queue_tolerance: 100,
+            queue_widening: 10,
+            queue_interval: Duration::from_secs(1),
+            default_rating: 1000,
+            default_max_players: 4,
+            max_players_limit: 8,
+            code_length: 6,
+            code_alphabet: DEFAULT_CODE_ALPHABET.chars().collect(),
+            code_blocklist: DEFAULT_CODE_BLOCKLIST
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+            stun_urls: vec![DEFAULT_STUN_URL.to_owned()],
+            turn_urls: Vec::new(),
+            turn_secret: None,
+            turn_ttl: Duration::from_secs(24 * 60 * 60),
+            rate_limit_requests: 20,
+            rate_limit_signaling: 200,
+        }
+    }
+}
+
+impl RoomConfig {
+    /// read the config from the environment, falling back to the defaults
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            trust_proxy: env_or("TRUST_PROXY", default.trust_proxy),
+            empty_room_grace: env_secs("ROOM_EMPTY_GRACE_SECS", default.empty_room_grace),
+            // a zero interval would never yield to the actor
+            sweep_interval: env_secs("ROOM_SWEEP_INTERVAL_SECS", default.sweep_interval)
+                .max(Duration::from_secs(1)),
+            resume_window: env_secs("RESUME_WINDOW_SECS", default.resume_window),
+            match_countdown: env_secs("MATCH_COUNTDOWN_SECS", default.match_countdown),
+            allow_spectators: env_or("ROOM_ALLOW_SPECTATORS", default.allow_spectators),
+            roster_size: env_or("ROSTER_SIZE", default.roster_size),
+            unique_characters: env_or("ROOM_UNIQUE_CHARACTERS", default.unique_characters),
+            max_time_limit: env_secs("ROOM_MAX_TIME_LIMIT_SECS", default.max_time_limit),
+            queue_tolerance: env_or("MATCHMAKING_TOLERANCE", default.queue_tolerance),
+            queue_widening: env_or("MATCHMAKING_WIDENING", default.queue_widening),
+            // a zero interval would never yield to the actor
+            queue_interval: env_secs("MATCHMAKING_INTERVAL_SECS", default.queue_interval)
+                .max(Duration::from_secs(1)),
+            default_rating: env_or("MATCHMAKING_DEFAULT_RATING", default.default_rating),
+            default_max_players: env_or("ROOM_DEFAULT_MAX_PLAYERS", default.default_max_players)
+                .max(1),
+            max_players_limit: env_or("ROOM_MAX_PLAYERS_LIMIT", default.max_players_limit).max(1),
+            code_length: env_or("ROOM_CODE_LENGTH", default.code_length).max(1),
+            code_alphabet: env::var("ROOM_CODE_ALPHABET")
+                .ok()
+                .map(|alphabet| alphabet.to_lowercase().chars().collect::<Vec<_>>())
+                .filter(|alphabet| !alphabet.is_empty())
+                .unwrap_or(default.code_alphabet),
+            // extra words are added on top of the built-in list
+            code_blocklist: default
+                .code_blocklist
+                .into_iter()
+                .chain(
+                    env_list("ROOM_CODE_BLOCKLIST")
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|word| word.to_lowercase()),
+                )
+                .collect(),
+            stun_urls: env_list("STUN_URLS").unwrap_or(default.stun_urls),
+            turn_urls: env_list("TURN_URLS").unwrap_or(default.turn_urls),
+            turn_secret: env::var("TURN_SECRET").ok().filter(|s| !s.is_empty()),
+            turn_ttl: env_secs("TURN_CREDENTIAL_TTL_SECS", default.turn_ttl),
+            rate_limit_requests: env_or("RATE_LIMIT_REQUESTS", default.rate_limit_requests),
+            rate_limit_signaling: env_or("RATE_LIMIT_SIGNALING", default.rate_limit_signaling),
+        }
+    }
+
+    /// the number of seats for a new room, given the size its creator asked for
+    pub fn room_size(&self, requested: Option<usize>) -> usize {
+        requested
+            .unwrap_or(self.default_max_players)
+            .clamp(1, self.max_players_limit)
+    }
+
+    /// check room settings requested by a host, describing the first problem found
+    pub fn validate_settings(&self, settings: &RoomSettings) -> Result<(), String> {
+        match settings.time_limit {
+            Some(0) => return Err("the time limit must be at least a second".to_owned()),
+            Some(limit) if u64::from(limit) > self.max_time_limit.as_secs() => {
+                return Err(format!(
+                    "the time limit can be at most {} seconds",
+                    self.max_time_limit.as_secs()
+                ))
+            }
+            None if settings.mode == GameMode::Time => {
+                return Err("time mode needs a time limit".to_owned())
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// whether a room code contains a blocked word
+    pub fn is_blocked_code(&self, code: &str) -> bool {
+        self.code_blocklist
+            .iter()
+            .any(|word| code.contains(word.as_str()))
+    }
+}
+
+/// parse an environment variable, using `default` when it is unset or invalid
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    match env::var(key) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            log::warn!("ignoring invalid value {:?} for {}", value, key);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// read a comma-separated list from an environment variable, if it is set
+fn env_list(key: &str) -> Option<Vec<String>> {
+    env::var(key).ok().map(|value| {
+        value
+            .split(',')
+            .map(|item| item.trim().to_owned())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+/// parse an environment variable holding a whole number of seconds
+fn env_secs(key: &str, default: Duration) -> Duration {
+    Duration::from_secs(env_or(key, default.as_secs()))
+}
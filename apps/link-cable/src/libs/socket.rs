@@ -1,3 +1,9 @@
+/// ICE servers and TURN credentials for clients
+pub mod ice;
+/// player ID generation
+pub mod ids;
+/// queue that groups players into matches
+pub mod matchmaker;
 /// requests from the client
 pub mod requests;
 /// responses to the client
@@ -0,0 +1,95 @@
+//! ICE server configuration for clients. TURN servers get short-lived
+//! credentials following the TURN REST API convention used by coturn: the
+//! username is the expiry timestamp and the password is an HMAC of it, keyed
+//! with a secret shared with the TURN server.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::libs::config::RoomConfig;
+
+use super::responses::{IceConfig, IceServer};
+
+/// the ICE servers a client should use, with TURN credentials valid from `now`
+pub fn ice_config(config: &RoomConfig, now: SystemTime) -> IceConfig {
+    let mut ice_servers = Vec::new();
+    if !config.stun_urls.is_empty() {
+        ice_servers.push(IceServer {
+            urls: config.stun_urls.clone(),
+            username: None,
+            credential: None,
+        });
+    }
+    if let Some(secret) = config.turn_secret.as_deref() {
+        if !config.turn_urls.is_empty() {
+            let expiry = now
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .saturating_add(config.turn_ttl)
+                .as_secs();
+            let username = expiry.to_string();
+            ice_servers.push(IceServer {
+                urls: config.turn_urls.clone(),
+                credential: Some(turn_password(secret, &username)),
+                username: Some(username),
+            });
+        }
+    }
+    IceConfig { ice_servers }
+}
+
+/// the TURN password for a username: the base64 HMAC-SHA1 of the username
+fn turn_password(secret: &str, username: &str) -> String {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(username.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn turn_password_is_base64_hmac_sha1() {
+        // RFC 2202, HMAC-SHA1 test case 2
+        assert_eq!(
+            turn_password("Jefe", "what do ya want for nothing?"),
+            "7/zfauXrL6LSdBbV8YTfnCWafHk="
+        );
+    }
+
+    #[test]
+    fn turn_username_is_the_expiry() {
+        let config = RoomConfig {
+            turn_urls: vec!["turn:turn.example.com:3478".to_owned()],
+            turn_secret: Some("north".to_owned()),
+            turn_ttl: Duration::from_secs(86400),
+            ..RoomConfig::default()
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ice = ice_config(&config, now);
+        let turn = &ice.ice_servers[1];
+        assert_eq!(turn.username.as_deref(), Some("1700086400"));
+        assert_eq!(
+            turn.credential.as_deref(),
+            Some("K+RlgFj3QLvB7WAVHfjfgMgyBeA=")
+        );
+    }
+
+    #[test]
+    fn turn_is_left_out_without_a_secret() {
+        let config = RoomConfig {
+            turn_urls: vec!["turn:turn.example.com:3478".to_owned()],
+            ..RoomConfig::default()
+        };
+        let ice = ice_config(&config, SystemTime::now());
+        assert_eq!(ice.ice_servers.len(), 1);
+        assert!(ice.ice_servers[0].credential.is_none());
+    }
+}
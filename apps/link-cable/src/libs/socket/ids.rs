@@ -0,0 +1,35 @@
+//! Player ID generation. A scheme only produces candidate IDs, `RoomServer`
+//! takes care of making sure they are unique.
+
+use rand::{distributions::Alphanumeric, rngs::ThreadRng, Rng};
+
+/// a way of generating player IDs
+pub trait IdScheme {
+    /// generate a new candidate ID
+    fn generate(&mut self) -> String;
+}
+
+/// random alphanumeric IDs of a fixed length
+pub struct AlphanumericIds {
+    rng: ThreadRng,
+    len: usize,
+}
+
+impl Default for AlphanumericIds {
+    fn default() -> Self {
+        Self {
+            rng: rand::thread_rng(),
+            len: 8,
+        }
+    }
+}
+
+impl IdScheme for AlphanumericIds {
+    fn generate(&mut self) -> String {
+        (&mut self.rng)
+            .sample_iter(&Alphanumeric)
+            .take(self.len)
+            .map(char::from)
+            .collect()
+    }
+}
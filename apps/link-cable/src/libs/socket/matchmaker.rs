@@ -0,0 +1,326 @@
+//! `Matchmaker` is an actor that holds sessions waiting for a match. It groups
+//! players of a similar rating, accepting wider rating gaps the longer they
+//! wait, and asks `RoomServer` to create a room for each group it forms.
+
+use std::{collections::HashSet, time::Instant};
+
+use actix::prelude::*;
+
+use crate::libs::config::RoomConfig;
+
+use super::{
+    responses::{GameMode, QueueStatus, Response},
+    server::{self, RoomServer},
+    session,
+};
+
+/// Add a session to the queue, replacing its previous entry if it has one
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct Enqueue {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
+    /// the number of players to be matched together
+    pub party_size: Option<usize>,
+    pub mode: GameMode,
+    /// the player's skill rating. Uses the server default when absent
+    pub rating: Option<u32>,
+}
+
+/// Take a session out of the queue
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct Dequeue {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
+}
+
+/// what a queued session wants from a match
+#[derive(Clone, Debug)]
+struct Preferences {
+    party_size: usize,
+    mode: GameMode,
+    rating: u32,
+    queued_at: Instant,
+}
+
+impl Preferences {
+    /// how far apart in rating this player accepts others, which grows the
+    /// longer they have been waiting
+    fn tolerance(&self, config: &RoomConfig, now: Instant) -> u32 {
+        let waited = now.saturating_duration_since(self.queued_at).as_secs() as u32;
+        config
+            .queue_tolerance
+            .saturating_add(config.queue_widening.saturating_mul(waited))
+    }
+
+    /// whether two players can be put in the same match
+    fn matches(&self, other: &Preferences, config: &RoomConfig, now: Instant) -> bool {
+        self.mode == other.mode
+            && self.party_size == other.party_size
+            && self.rating.abs_diff(other.rating)
+                <= self
+                    .tolerance(config, now)
+                    .min(other.tolerance(config, now))
+    }
+}
+
+/// a session waiting for a match
+#[derive(Debug)]
+struct Ticket {
+    addr: Addr<session::PlayerSession>,
+    preferences: Preferences,
+}
+
+/// group queued players into matches, starting with whoever has waited the
+/// longest. Returns the queue indices of each group, the longest waiting
+/// player first
+fn form_groups(queue: &[Preferences], config: &RoomConfig, now: Instant) -> Vec<Vec<usize>> {
+    let mut matched = HashSet::new();
+    let mut groups = Vec::new();
+    for (i, ticket) in queue.iter().enumerate() {
+        if matched.contains(&i) {
+            continue;
+        }
+        let mut candidates: Vec<usize> = queue
+            .iter()
+            .enumerate()
+            .filter(|&(j, other)| {
+                j != i && !matched.contains(&j) && ticket.matches(other, config, now)
+            })
+            .map(|(j, _)| j)
+            .collect();
+        // prefer the players closest to this one in rating
+        candidates.sort_by_key(|&j| ticket.rating.abs_diff(queue[j].rating));
+        // everyone in a group has to accept everyone else in it
+        let mut group = vec![i];
+        for j in candidates {
+            if group.len() == ticket.party_size {
+                break;
+            }
+            if group
+                .iter()
+                .all(|&member| queue[member].matches(&queue[j], config, now))
+            {
+                group.push(j);
+            }
+        }
+        if group.len() < ticket.party_size {
+            continue;
+        }
+        matched.extend(group.iter().copied());
+        groups.push(group);
+    }
+    groups
+}
+
+/// `Matchmaker` manages the queue of sessions looking for a match
+pub struct Matchmaker {
+    /// the server that rooms are created on
+    hub: Addr<RoomServer>,
+    /// queued sessions, longest waiting first
+    queue: Vec<Ticket>,
+    config: RoomConfig,
+}
+
+impl Matchmaker {
+    pub fn new(hub: Addr<RoomServer>, config: RoomConfig) -> Self {
+        Self {
+            hub,
+            queue: Vec::new(),
+            config,
+        }
+    }
+
+    /// group queued sessions into matches and hand each group to the room server
+    fn match_queue(&mut self) {
+        let preferences: Vec<Preferences> =
+            self.queue.iter().map(|t| t.preferences.clone()).collect();
+        let groups = form_groups(&preferences, &self.config, Instant::now());
+        if groups.is_empty() {
+            return;
+        }
+
+        let mut tickets: Vec<Option<Ticket>> = self.queue.drain(..).map(Some).collect();
+        for group in groups {
+            let members: Vec<Ticket> = group.iter().filter_map(|&i| tickets[i].take()).collect();
+            log::info!(
+                "matched {} players for a {:?} match",
+                members.len(),
+                members[0].preferences.mode
+            );
+            for member in &members {
+                member
+                    .addr
+                    .do_send(Response::Queue(QueueStatus { queued: false }));
+            }
+            self.hub.do_send(server::CreateMatch {
+                mode: members[0].preferences.mode,
+                members: members.into_iter().map(|t| t.addr).collect(),
+            });
+        }
+        self.queue = tickets.into_iter().flatten().collect();
+    }
+}
+
+impl Actor for Matchmaker {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.config.queue_interval, |act, _| act.match_queue());
+    }
+}
+
+/// put a session in the queue
+impl Handler<Enqueue> for Matchmaker {
+    type Result = ();
+
+    fn handle(&mut self, msg: Enqueue, _: &mut Context<Self>) {
+        let Enqueue {
+            addr,
+            party_size,
+            mode,
+            rating,
+        } = msg;
+        self.queue.retain(|t| t.addr != addr);
+        let ticket = Ticket {
+            addr: addr.clone(),
+            preferences: Preferences {
+                party_size: self.config.room_size(party_size),
+                mode,
+                rating: rating.unwrap_or(self.config.default_rating),
+                queued_at: Instant::now(),
+            },
+        };
+        log::info!("queued {:?}", ticket);
+        self.queue.push(ticket);
+        addr.do_send(Response::Queue(QueueStatus { queued: true }));
+    }
+}
+
+/// take a session out of the queue
+impl Handler<Dequeue> for Matchmaker {
+    type Result = ();
+
+    fn handle(&mut self, msg: Dequeue, _: &mut Context<Self>) {
+        let before = self.queue.len();
+        self.queue.retain(|t| t.addr != msg.addr);
+        if self.queue.len() != before {
+            msg.addr
+                .do_send(Response::Queue(QueueStatus { queued: false }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// a player who queued `waited` seconds before `now`. Tests take `now` a
+    /// minute after `start()` so that this never reaches before the clock began
+    fn player(rating: u32, waited: u64, now: Instant) -> Preferences {
+        Preferences {
+            party_size: 2,
+            mode: GameMode::Stock,
+            rating,
+            queued_at: now - Duration::from_secs(waited),
+        }
+    }
+
+    /// an instant far enough ahead of the clock for `player` to wait back from
+    fn start() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    #[test]
+    fn groups_players_within_tolerance() {
+        let config = RoomConfig::default();
+        let now = start();
+        let queue = [
+            player(1000, 0, now),
+            player(1500, 0, now),
+            player(1050, 0, now),
+        ];
+        assert_eq!(form_groups(&queue, &config, now), vec![vec![0, 2]]);
+    }
+
+    #[test]
+    fn prefers_the_closest_rating() {
+        let config = RoomConfig::default();
+        let now = start();
+        let queue = [
+            player(1000, 0, now),
+            player(1090, 0, now),
+            player(1010, 0, now),
+        ];
+        assert_eq!(form_groups(&queue, &config, now), vec![vec![0, 2]]);
+    }
+
+    #[test]
+    fn tolerance_widens_while_waiting() {
+        let config = RoomConfig::default();
+        let now = start();
+        // 300 apart needs 20 seconds of waiting from both players
+        let queue = [player(1000, 10, now), player(1300, 30, now)];
+        assert!(form_groups(&queue, &config, now).is_empty());
+        let queue = [player(1000, 20, now), player(1300, 30, now)];
+        assert_eq!(form_groups(&queue, &config, now), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn only_matches_the_same_mode_and_party_size() {
+        let config = RoomConfig::default();
+        let now = start();
+        let timed = Preferences {
+            mode: GameMode::Time,
+            ..player(1000, 0, now)
+        };
+        let trio = Preferences {
+            party_size: 3,
+            ..player(1000, 0, now)
+        };
+        let queue = [player(1000, 0, now), timed, trio];
+        assert!(form_groups(&queue, &config, now).is_empty());
+    }
+
+    #[test]
+    fn waits_until_the_party_is_full() {
+        let config = RoomConfig::default();
+        let now = start();
+        let trio = |rating| Preferences {
+            party_size: 3,
+            ..player(rating, 0, now)
+        };
+        assert!(form_groups(&[trio(1000), trio(1000)], &config, now).is_empty());
+        assert_eq!(
+            form_groups(
+                &[trio(1000), trio(1000), trio(1000), trio(1000)],
+                &config,
+                now
+            ),
+            vec![vec![0, 1, 2]]
+        );
+    }
+
+    #[test]
+    fn every_member_accepts_every_other() {
+        let config = RoomConfig::default();
+        let now = start();
+        let trio = |rating| Preferences {
+            party_size: 3,
+            ..player(rating, 0, now)
+        };
+        // both are close to the first player but 200 apart from each other
+        assert!(form_groups(&[trio(1000), trio(900), trio(1100)], &config, now).is_empty());
+        assert_eq!(
+            form_groups(
+                &[trio(1000), trio(900), trio(1100), trio(1050)],
+                &config,
+                now
+            ),
+            vec![vec![0, 3, 2]]
+        );
+    }
+}
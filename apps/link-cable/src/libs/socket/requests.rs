@@ -2,6 +2,8 @@ use actix::Message;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::responses::{GameMode, RoomSettings, Topology};
+
 /// The message type for player decisions
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PlayerChoice {
@@ -17,7 +19,132 @@ pub struct Join {
 
 /// The message type used for requesting a new room
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Create {}
+pub struct Create {
+    /// the number of seats in the room. Uses the server default when absent
+    #[serde(default)]
+    pub max_players: Option<usize>,
+    /// the room's settings. Uses the defaults when absent
+    #[serde(default)]
+    pub settings: Option<RoomSettings>,
+}
+
+/// The message type used for joining any public room
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuickMatch {}
+
+/// The message type used for entering the matchmaking queue
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Queue {
+    /// the number of players to be matched together. Uses the server default
+    /// room size when absent
+    #[serde(default)]
+    pub party_size: Option<usize>,
+    #[serde(default)]
+    pub mode: GameMode,
+    /// the player's skill rating. Uses the server default when absent
+    #[serde(default)]
+    pub rating: Option<u32>,
+}
+
+/// The message type used for leaving the matchmaking queue
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Unqueue {}
+
+/// The message type used for leaving the current room
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Leave {}
+
+/// The message type used for reclaiming a seat after losing connection
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Resume {
+    /// the token from the `you` response
+    pub token: String,
+}
+
+/// The message type used for moving to another seat in the room
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Seat {
+    /// the seat index to move to
+    pub i: usize,
+}
+
+/// The message type used by the host to hand the host role to another player
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Host {
+    /// the ID of the new host
+    pub id: String,
+}
+
+/// The message type used by the host to remove a player from the room
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Kick {
+    /// the ID of the player to remove
+    pub id: String,
+    /// an explanation shown to the removed player
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// The message type used for marking the player as ready to start
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ready {
+    pub ready: bool,
+}
+
+/// The message type used by the host to start the match
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Start {}
+
+/// The message type used by the host to end the match
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct End {}
+
+/// The message type used by the host to bring everyone back to the lobby
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Lobby {}
+
+/// The message type used by the host to let spectators into the WebRTC mesh
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Spectators {
+    pub mesh: bool,
+}
+
+/// The message type used by the host to change the room's settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Settings {
+    pub settings: SettingsPatch,
+}
+
+/// Changes to a room's settings. Settings that are absent keep their value
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SettingsPatch {
+    #[serde(default)]
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub mode: Option<GameMode>,
+    #[serde(default)]
+    pub time_limit: Option<u32>,
+    #[serde(default)]
+    pub public: Option<bool>,
+    #[serde(default)]
+    pub teams: Option<bool>,
+    #[serde(default)]
+    pub topology: Option<Topology>,
+}
+
+impl SettingsPatch {
+    /// the settings with these changes made to them
+    pub fn apply(self, settings: &RoomSettings) -> RoomSettings {
+        RoomSettings {
+            seed: self.seed.unwrap_or(settings.seed),
+            mode: self.mode.unwrap_or(settings.mode),
+            time_limit: self.time_limit.or(settings.time_limit),
+            public: self.public.unwrap_or(settings.public),
+            teams: self.teams.unwrap_or(settings.teams),
+            topology: self.topology.unwrap_or(settings.topology),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SDPOffer {
@@ -29,12 +156,24 @@ pub struct SDPOffer {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Offer {
     pub offer: SDPOffer,
+    /// the ID of the player the offer is for. Sent to the whole room when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// the ID of the player the offer is from, filled in by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
 }
 
 /// The message type used for webRTC peer connection
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct IceCandidate {
     pub candidate: Value,
+    /// the ID of the player the candidate is for. Sent to the whole room when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// the ID of the player the candidate is from, filled in by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
 }
 
 /// client-session messaging- main message structure received from the client
@@ -51,6 +190,51 @@ pub enum Request {
     #[serde(rename = "create")]
     Create(Create),
 
+    #[serde(rename = "quickmatch")]
+    QuickMatch(QuickMatch),
+
+    #[serde(rename = "queue")]
+    Queue(Queue),
+
+    #[serde(rename = "unqueue")]
+    Unqueue(Unqueue),
+
+    #[serde(rename = "leave")]
+    Leave(Leave),
+
+    #[serde(rename = "resume")]
+    Resume(Resume),
+
+    #[serde(rename = "seat")]
+    Seat(Seat),
+
+    #[serde(rename = "host")]
+    Host(Host),
+
+    #[serde(rename = "kick")]
+    Kick(Kick),
+
+    #[serde(rename = "ban")]
+    Ban(Kick),
+
+    #[serde(rename = "ready")]
+    Ready(Ready),
+
+    #[serde(rename = "start")]
+    Start(Start),
+
+    #[serde(rename = "end")]
+    End(End),
+
+    #[serde(rename = "lobby")]
+    Lobby(Lobby),
+
+    #[serde(rename = "spectators")]
+    Spectators(Spectators),
+
+    #[serde(rename = "settings")]
+    Settings(Settings),
+
     #[serde(rename = "offer")]
     Offer(Offer),
     #[serde(rename = "answer")]
@@ -59,3 +243,40 @@ pub enum Request {
     #[serde(rename = "ice")]
     IceCandidate(IceCandidate),
 }
+
+impl Request {
+    /// the `cmd` tag of the request
+    pub fn cmd(&self) -> &'static str {
+        match self {
+            Request::PlayerChoice(_) => "choice",
+            Request::Join(_) => "join",
+            Request::Create(_) => "create",
+            Request::QuickMatch(_) => "quickmatch",
+            Request::Queue(_) => "queue",
+            Request::Unqueue(_) => "unqueue",
+            Request::Leave(_) => "leave",
+            Request::Resume(_) => "resume",
+            Request::Seat(_) => "seat",
+            Request::Host(_) => "host",
+            Request::Kick(_) => "kick",
+            Request::Ban(_) => "ban",
+            Request::Ready(_) => "ready",
+            Request::Start(_) => "start",
+            Request::End(_) => "end",
+            Request::Lobby(_) => "lobby",
+            Request::Spectators(_) => "spectators",
+            Request::Settings(_) => "settings",
+            Request::Offer(_) => "offer",
+            Request::Answer(_) => "answer",
+            Request::IceCandidate(_) => "ice",
+        }
+    }
+
+    /// whether the request is WebRTC signaling passed on to another player
+    pub fn is_signaling(&self) -> bool {
+        matches!(
+            self,
+            Request::Offer(_) | Request::Answer(_) | Request::IceCandidate(_)
+        )
+    }
+}
@@ -1,5 +1,5 @@
 use actix::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use typeshare::typeshare;
 
 use super::requests::{IceCandidate, Offer};
@@ -11,7 +11,194 @@ pub struct Player {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub c: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[typeshare(serialized_as = "Option<u32>")]
     pub i: Option<usize>,
+    /// whether the player is ready to start the match
+    pub ready: bool,
+    /// whether the player is watching the match rather than playing in it
+    pub spectator: bool,
+}
+
+/// the player's own info, sent only to them
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct You {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub c: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[typeshare(serialized_as = "Option<u32>")]
+    pub i: Option<usize>,
+    /// secret used to resume the session after losing connection
+    pub token: String,
+    /// whether the player is watching the match rather than playing in it
+    pub spectator: bool,
+}
+
+/// a player and their seat, for events about that player
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct PlayerStatus {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[typeshare(serialized_as = "Option<u32>")]
+    pub i: Option<usize>,
+}
+
+/// the stage of a room's lifecycle
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum RoomState {
+    /// players are gathering and getting ready
+    #[default]
+    Lobby,
+    /// the match is about to start
+    Countdown,
+    /// the match is being played
+    InMatch,
+    /// the match is over
+    PostMatch,
+}
+
+/// how a match is won
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum GameMode {
+    /// the last player with lives left wins
+    #[default]
+    Stock,
+    /// the player with the most knockouts when time runs out wins
+    Time,
+}
+
+/// how the players of a room connect to each other over WebRTC
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum Topology {
+    /// every player connects to every other player
+    #[default]
+    Mesh,
+    /// every player connects to the host only
+    Star,
+}
+
+/// which side of a WebRTC connection a player is on
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum PeerRole {
+    /// creates the offer
+    Offerer,
+    /// waits for the offer and answers it
+    Answerer,
+}
+
+/// an instruction to open a WebRTC connection with another player
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct ConnectTo {
+    pub peer_id: String,
+    pub role: PeerRole,
+}
+
+/// per-room settings chosen by the host, shared with every player so that
+/// all clients generate the same world
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[typeshare]
+pub struct RoomSettings {
+    /// seed for generating the map. A random one is picked when absent
+    #[serde(default = "random_seed")]
+    pub seed: u32,
+    #[serde(default)]
+    pub mode: GameMode,
+    /// length of a match in seconds. Required in `Time` mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<u32>,
+    /// whether the room is listed for anyone to join, rather than only by code
+    #[serde(default)]
+    pub public: bool,
+    /// whether players play in teams
+    #[serde(default)]
+    pub teams: bool,
+    /// how players connect to each other. Changing it only affects
+    /// connections planned afterwards
+    #[serde(default)]
+    pub topology: Topology,
+}
+
+impl RoomSettings {
+    /// the default settings for a game mode
+    pub fn for_mode(mode: GameMode) -> Self {
+        Self {
+            mode,
+            time_limit: (mode == GameMode::Time).then_some(DEFAULT_TIME_LIMIT),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            seed: random_seed(),
+            mode: GameMode::default(),
+            time_limit: None,
+            public: false,
+            teams: false,
+            topology: Topology::default(),
+        }
+    }
+}
+
+/// length of a timed match in seconds, when the host does not choose one
+const DEFAULT_TIME_LIMIT: u32 = 180;
+
+fn random_seed() -> u32 {
+    rand::random()
+}
+
+/// a STUN or TURN server, in the shape of `RTCIceServer`
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+/// the ICE servers the client should use for its WebRTC connections
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct IceConfig {
+    pub ice_servers: Vec<IceServer>,
+}
+
+/// whether the player is waiting in the matchmaking queue
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct QueueStatus {
+    pub queued: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct StateInfo {
+    pub state: RoomState,
+    /// seconds until the match starts, during the countdown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countdown: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct Ready {
+    pub id: String,
+    pub ready: bool,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -19,6 +206,64 @@ pub struct Player {
 pub struct RoomInfo {
     pub code: String,
     pub players: Vec<Option<Player>>,
+    /// the ID of the room's host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub state: RoomState,
+    pub settings: RoomSettings,
+    /// players watching the room without a seat
+    pub spectators: Vec<Player>,
+    /// whether spectators take part in the WebRTC mesh
+    pub spectator_mesh: bool,
+}
+
+/// why a player is no longer in a room
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum LeaveReason {
+    /// the player left or closed their connection
+    Left,
+    /// the player's connection stopped responding
+    Timeout,
+    /// the player was removed by the host
+    Kicked,
+    /// the player was removed by the host, and may not come back
+    Banned,
+    /// the player's session was resumed from another connection
+    Resumed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct PlayerLeft {
+    pub id: String,
+    /// the seat the player was in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[typeshare(serialized_as = "Option<u32>")]
+    pub i: Option<usize>,
+    pub reason: LeaveReason,
+    /// an explanation for the player who was removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// a short overview of a room, as listed by `GET /rooms`
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct RoomSummary {
+    pub code: String,
+    /// number of players currently in the room
+    #[typeshare(serialized_as = "u32")]
+    pub players: usize,
+    /// number of seats still available
+    #[typeshare(serialized_as = "u32")]
+    pub open: usize,
+    /// unix timestamp (seconds) of when the room was created
+    #[typeshare(serialized_as = "number")]
+    pub created_at: u64,
+    /// whether a match is currently being played in the room
+    pub in_match: bool,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -27,6 +272,61 @@ pub struct Chat {
     pub msg: String,
 }
 
+/// the reason a request could not be fulfilled
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[typeshare]
+pub enum ErrorCode {
+    /// no room exists with the requested code
+    RoomNotFound,
+    /// the room has no free seats
+    RoomFull,
+    /// the requested seat is taken or does not exist
+    SeatUnavailable,
+    /// the resume token is unknown or has expired
+    ResumeFailed,
+    /// the request can only be made by the room's host
+    NotHost,
+    /// the player the request refers to is not in the room, or is spectating
+    /// when the request needs a seated player
+    PlayerNotFound,
+    /// the player is banned from the room
+    Banned,
+    /// the player could not be banned because their address is unknown
+    BanFailed,
+    /// the request is not allowed in the room's current state
+    InvalidState,
+    /// not every player is ready to start the match
+    NotReady,
+    /// the chosen character is not in the roster
+    InvalidCharacter,
+    /// another player in the room already chose the character
+    CharacterTaken,
+    /// the requested room settings are not valid
+    InvalidSettings,
+    /// the room's match has started and it does not take spectators
+    MatchInProgress,
+    /// a new room could not be created
+    RoomCreationFailed,
+    /// the request is only valid while in a room
+    NotInRoom,
+    /// the request could not be parsed
+    MalformedRequest,
+    /// the client is sending requests too quickly
+    RateLimited,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[typeshare]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    /// a human-readable description of the error
+    pub message: String,
+    /// the `cmd` of the request that failed, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_cmd: Option<String>,
+}
+
 /// client-session messaging- main message structure received from the client
 #[derive(Message, Serialize, Clone, Debug)]
 #[rtype(result = "()")]
@@ -35,13 +335,34 @@ pub enum Response {
     #[serde(rename = "room")]
     RoomInfo(RoomInfo),
     #[serde(rename = "you")]
-    You(Player),
+    You(You),
     #[serde(rename = "player")]
     Player(Player),
+    #[serde(rename = "left")]
+    PlayerLeft(PlayerLeft),
+    #[serde(rename = "disconnected")]
+    Disconnected(PlayerStatus),
+    #[serde(rename = "reconnected")]
+    Reconnected(PlayerStatus),
+    #[serde(rename = "host")]
+    Host(PlayerStatus),
+    #[serde(rename = "ready")]
+    Ready(Ready),
+    #[serde(rename = "state")]
+    State(StateInfo),
+    #[serde(rename = "queue")]
+    Queue(QueueStatus),
+    #[serde(rename = "connect_to")]
+    ConnectTo(ConnectTo),
+    #[serde(rename = "ice_config")]
+    IceConfig(IceConfig),
     #[serde(rename = "chat")]
+    #[allow(dead_code)]
     Chat(Chat),
     #[serde(rename = "alert")]
     Alert(Chat),
+    #[serde(rename = "error")]
+    Error(ErrorInfo),
     #[serde(rename = "offer")]
     Offer(Offer),
     #[serde(rename = "answer")]
@@ -49,3 +370,14 @@ pub enum Response {
     #[serde(rename = "ice")]
     IceCandidate(IceCandidate),
 }
+
+impl Response {
+    /// an error response to the request with the given `cmd`
+    pub fn error(code: ErrorCode, request_cmd: Option<&str>, message: impl Into<String>) -> Self {
+        Response::Error(ErrorInfo {
+            code,
+            message: message.into(),
+            request_cmd: request_cmd.map(str::to_owned),
+        })
+    }
+}
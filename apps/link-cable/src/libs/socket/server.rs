@@ -2,24 +2,64 @@
 //! And manages available rooms. Peers send messages to other peers in same
 //! room through `RoomServer`.
 
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use actix::prelude::*;
-use rand::{self, distributions::Alphanumeric, rngs::ThreadRng, Rng};
+use actix_web::web::Bytes;
+use rand::{self, distributions::Alphanumeric, rngs::ThreadRng, seq::SliceRandom, Rng};
+
+use crate::libs::config::RoomConfig;
 
 use super::{
-    responses::{self, Player, Response, RoomInfo},
+    ice,
+    ids::{AlphanumericIds, IdScheme},
+    requests::SettingsPatch,
+    responses::{
+        self, ConnectTo, ErrorCode, GameMode, LeaveReason, PeerRole, Player, PlayerLeft,
+        PlayerStatus, Ready, Response, RoomInfo, RoomSettings, RoomState, RoomSummary, StateInfo,
+        Topology, You,
+    },
     session::{self},
 };
 
+/// New session is connected
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct Connect {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
+    /// the client's IP address, if known
+    pub ip: Option<String>,
+}
+
 /// Session is disconnected
 #[derive(Message, Clone, Debug)]
 #[rtype(result = "()")]
 pub struct Disconnect {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
     /// the player ID
     pub id: Option<String>,
     /// the room code
     pub room: Option<String>,
+    /// why the session ended
+    pub reason: LeaveReason,
+}
+
+/// Player leaves their room but keeps the session open
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct LeaveRoom {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
+    /// the player ID
+    pub id: String,
+    /// the room code
+    pub room: String,
 }
 
 /// Join room by provided code
@@ -32,15 +72,168 @@ pub struct JoinRoom {
     /// 4-digit room code
     pub code: String,
 }
+
+/// Reclaim a player's seat in a room with the token issued to them
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct ResumeSession {
+    /// the new client session address
+    pub addr: Addr<session::PlayerSession>,
+    /// the resume token from the player's `You` response
+    pub token: String,
+}
+
+/// Move a player to another seat in their room
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct ChooseSeat {
+    /// the player ID
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the seat index to move to
+    pub seat: usize,
+}
+
+/// Hand the host role to another player
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct TransferHost {
+    /// the ID of the player making the request
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the ID of the player to make host
+    pub to: String,
+}
+
+/// Remove a player from the room, optionally banning them
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct KickPlayer {
+    /// the ID of the player making the request
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the ID of the player to remove
+    pub target: String,
+    /// an explanation shown to the removed player
+    pub reason: Option<String>,
+    /// whether the player may come back to the room
+    pub ban: bool,
+}
+
+/// Mark a player as ready, or not ready, to start the match
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct SetReady {
+    /// the player ID
+    pub id: String,
+    /// the room code
+    pub room: String,
+    pub ready: bool,
+}
+
+/// Move the room to another stage of its lifecycle at the host's request
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct ChangeState {
+    /// the ID of the player making the request
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the requested state. Requesting `Countdown` starts the match
+    pub state: RoomState,
+}
+
+/// Choose the character a player will play as
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct ChooseCharacter {
+    /// the player ID
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// selected character
+    pub c: u8,
+}
+
+/// Let spectators take part in the room's WebRTC mesh, or keep them out of it
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct SetSpectatorMesh {
+    /// the ID of the player making the request
+    pub id: String,
+    /// the room code
+    pub room: String,
+    pub enabled: bool,
+}
+
 /// request to create a room
 #[derive(Message, Clone, Debug)]
 #[rtype(result = "()")]
 pub struct CreateRoom {
     /// client session address
     pub addr: Addr<session::PlayerSession>,
+    /// the number of seats requested by the player
+    pub max_players: Option<usize>,
+    /// the settings requested by the player
+    pub settings: Option<RoomSettings>,
 }
 
-/// directly send arbitrary messages to room members
+/// Join a public room with a free seat, or create one
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct QuickMatch {
+    /// client session address
+    pub addr: Addr<session::PlayerSession>,
+}
+
+/// Create a room for a group of players matched by the `Matchmaker`
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct CreateMatch {
+    /// the matched sessions, in seat order. The first one hosts the room
+    pub members: Vec<Addr<session::PlayerSession>>,
+    pub mode: GameMode,
+}
+
+/// Change a room's settings at the host's request
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct ChangeSettings {
+    /// the ID of the player making the request
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the settings to change, leaving the others as they are
+    pub changes: SettingsPatch,
+}
+
+/// how many random room codes are tried before giving up on creating a room
+const CODE_ATTEMPTS: usize = 64;
+
+/// length of the tokens players use to resume their session
+const TOKEN_LENGTH: usize = 32;
+
+/// request a summary of the rooms on the server
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "Vec<RoomSummary>")]
+pub struct ListRooms {
+    /// only list public rooms, as the lobby browser shows. Private rooms are
+    /// otherwise only found by their code
+    pub public_only: bool,
+}
+
+/// request the full info of a single room
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "Option<RoomInfo>")]
+pub struct GetRoom {
+    /// 4-digit room code
+    pub code: String,
+}
+
+/// directly send WebRTC signaling messages to room members
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct ToRoom {
@@ -52,6 +245,42 @@ pub struct ToRoom {
     pub id: String,
 }
 
+/// directly send a WebRTC signaling message to a single room member
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct ToPlayer {
+    pub msg: Response,
+    /// the room ID both players are in
+    pub room: String,
+    /// the player ID the message is from
+    pub id: String,
+    /// the player ID the message is for
+    pub to: String,
+    /// the `cmd` of the request the message came from
+    pub cmd: &'static str,
+}
+
+/// Relay a binary game frame to another player, or to the whole room, for
+/// when their WebRTC connection could not be made
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct Relay {
+    /// the player ID the frame is from
+    pub id: String,
+    /// the room code
+    pub room: String,
+    /// the seat of the player the frame is for. Goes to every other player in
+    /// the room when absent
+    pub to: Option<usize>,
+    /// the frame, starting with the seat of the player it is from
+    pub data: Bytes,
+}
+
+/// A binary game frame relayed from another player
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct Frame(pub Bytes);
+
 /// broadcast message to all users. Provide a room to scope the broadcast to that room
 #[derive(Message, Clone, Debug)]
 #[rtype(result = "()")]
@@ -66,6 +295,7 @@ impl Broadcast {
             text: msg,
         }
     }
+    #[allow(dead_code)]
     pub fn room(room: String, msg: String) -> Broadcast {
         Broadcast {
             room: Some(room),
@@ -80,8 +310,19 @@ pub struct PlayerInfo {
     id: String,
     /// the player's chosen character
     c: Option<u8>,
+    /// whether the player is ready to start the match
+    ready: bool,
+    /// whether the player is watching without a seat
+    spectator: bool,
     /// the address of the player's session
     addr: Addr<session::PlayerSession>,
+    /// the client's IP address, if known. Kept while the player can resume their
+    /// session, so that they can still be banned after dropping
+    ip: Option<String>,
+    /// secret the player can use to resume their session after losing connection
+    token: String,
+    /// when and why the player's session dropped, while they can still resume it
+    disconnected: Option<(Instant, LeaveReason)>,
 }
 
 /// a room containing player data
@@ -90,25 +331,561 @@ pub struct Room {
     code: String,
     /// map of player id to player info
     players: HashMap<String, PlayerInfo>,
-    /// an ordered list of player IDs
+    /// an ordered list of player IDs. Each index is a seat, empty seats are `None`
     players_order: Vec<Option<String>>,
+    /// the number of seats in the room
+    max_players: usize,
+    /// the ID of the player who owns the room. The host is the authoritative
+    /// peer of the match and the only player allowed to manage the room
+    host: Option<String>,
+    /// IP addresses of players banned from the room
+    banned: HashSet<String>,
+    /// when the room was created
+    created_at: SystemTime,
+    /// the stage of the room's lifecycle
+    state: RoomState,
+    /// incremented on every state change, so delayed transitions can tell
+    /// whether they are still current
+    generation: u64,
+    /// whether spectators take part in the WebRTC mesh
+    spectator_mesh: bool,
+    /// the host's choices for the match
+    settings: RoomSettings,
+    /// when the last player left the room, if it is empty
+    emptied_at: Option<Instant>,
+}
+
+impl Room {
+    fn new(code: String, max_players: usize, settings: RoomSettings) -> Self {
+        Self {
+            code,
+            players: HashMap::new(),
+            players_order: Vec::new(),
+            max_players,
+            host: None,
+            banned: HashSet::new(),
+            created_at: SystemTime::now(),
+            state: RoomState::Lobby,
+            generation: 0,
+            spectator_mesh: false,
+            settings,
+            emptied_at: None,
+        }
+    }
+
+    /// the room's players, in seat order
+    fn info(&self) -> RoomInfo {
+        RoomInfo {
+            players: self
+                .players_order
+                .iter()
+                .map(|item| item.as_deref().and_then(|id| self.player(id)))
+                .collect(),
+            code: self.code.clone(),
+            host: self.host.clone(),
+            state: self.state,
+            settings: self.settings.clone(),
+            spectators: self
+                .players
+                .values()
+                .filter(|p| p.spectator)
+                .filter_map(|p| self.player(&p.id))
+                .collect(),
+            spectator_mesh: self.spectator_mesh,
+        }
+    }
+
+    /// whether the player takes part in the WebRTC mesh. Spectators only do
+    /// when the host lets them
+    fn in_mesh(&self, id: &str) -> bool {
+        self.players
+            .get(id)
+            .is_some_and(|p| self.spectator_mesh || !p.spectator)
+    }
+
+    /// whether the match has started, so new players may only spectate
+    fn is_locked(&self) -> bool {
+        matches!(self.state, RoomState::Countdown | RoomState::InMatch)
+    }
+
+    /// the number of players in a seat
+    fn seated(&self) -> usize {
+        self.players_order.iter().flatten().count()
+    }
+
+    /// the lowest free seat, if the room is not full
+    fn free_seat(&self) -> Option<usize> {
+        self.players_order
+            .iter()
+            .position(Option::is_none)
+            .or(Some(self.players_order.len()))
+            .filter(|&seat| seat < self.max_players)
+    }
+
+    /// the seat a player is in
+    fn seat_of(&self, id: &str) -> Option<usize> {
+        self.players_order
+            .iter()
+            .position(|x| x.as_deref() == Some(id))
+    }
+
+    /// put a player in a seat, adding seats up to it if needed
+    fn take_seat(&mut self, seat: usize, id: String) {
+        if seat >= self.players_order.len() {
+            self.players_order.resize(seat + 1, None);
+        }
+        self.players_order[seat] = Some(id);
+    }
+
+    /// send a message to every player in the room
+    fn broadcast(&self, msg: Response) {
+        for p in self.players.values() {
+            p.addr.do_send(msg.clone());
+        }
+    }
+
+    /// send a message to every player in the room except one
+    fn broadcast_except(&self, id: &str, msg: Response) {
+        for p in self.players.values().filter(|p| p.id != id) {
+            p.addr.do_send(msg.clone());
+        }
+    }
+
+    /// move the room to another state and let the players know
+    fn set_state(&mut self, state: RoomState, countdown: Option<Duration>) {
+        log::info!("room {} is now in {:?}", self.code, state);
+        self.state = state;
+        self.generation += 1;
+        self.broadcast(Response::State(StateInfo {
+            state,
+            countdown: countdown.map(|d| d.as_secs() as u32),
+        }));
+    }
+
+    /// stop a running countdown, since the players in the room changed
+    fn cancel_countdown(&mut self) {
+        if self.state == RoomState::Countdown {
+            self.set_state(RoomState::Lobby, None);
+        }
+    }
+
+    /// whether the player is in the room and still connected
+    fn is_connected(&self, id: &str) -> bool {
+        self.players
+            .get(id)
+            .is_some_and(|p| p.disconnected.is_none())
+    }
+
+    /// make a player the host and let the room know
+    fn set_host(&mut self, id: String) {
+        log::info!("Player {} is now the host of room {}", id, self.code);
+        let status = PlayerStatus {
+            i: self.seat_of(&id),
+            id: id.clone(),
+        };
+        self.host = Some(id);
+        self.broadcast(Response::Host(status));
+    }
+
+    /// hand the host role to the next connected player after the given seat.
+    /// The room is left without a host if nobody is connected
+    fn migrate_host(&mut self, from: usize) {
+        let seats = self.players_order.len();
+        let next = (1..=seats)
+            .filter_map(|n| self.players_order[(from + n) % seats].as_ref())
+            .find(|id| self.is_connected(id))
+            .cloned();
+        match next {
+            Some(id) => self.hand_over_host(id),
+            None => self.host = None,
+        }
+    }
+
+    /// hand the host role over from another player. In a star, everyone has to
+    /// connect to the new host
+    fn hand_over_host(&mut self, id: String) {
+        self.set_host(id.clone());
+        if self.settings.topology == Topology::Star {
+            self.connect(&id);
+        }
+    }
+
+    /// the players a player connects to over WebRTC
+    fn peers_of(&self, id: &str) -> Vec<&PlayerInfo> {
+        if !self.in_mesh(id) {
+            return Vec::new();
+        }
+        let star = self.settings.topology == Topology::Star;
+        let is_host = self.host.as_deref() == Some(id);
+        self.players
+            .values()
+            .filter(|p| p.id != id && p.disconnected.is_none() && self.in_mesh(&p.id))
+            .filter(|p| !star || is_host || self.host.as_ref() == Some(&p.id))
+            .collect()
+    }
+
+    /// tell a player which peers to connect to, and those peers to expect
+    /// them. The player always makes the offer, so two peers never offer to
+    /// each other at the same time
+    fn connect(&self, id: &str) {
+        self.connect_skipping(id, &[]);
+    }
+
+    /// like `connect`, leaving out peers that will be told to connect to the
+    /// player themselves
+    fn connect_skipping(&self, id: &str, pending: &[String]) {
+        let Some(player) = self.players.get(id) else {
+            return;
+        };
+        let peers = self.peers_of(id);
+        for peer in peers.into_iter().filter(|p| !pending.contains(&p.id)) {
+            player.addr.do_send(Response::ConnectTo(ConnectTo {
+                peer_id: peer.id.clone(),
+                role: PeerRole::Offerer,
+            }));
+            peer.addr.do_send(Response::ConnectTo(ConnectTo {
+                peer_id: id.to_owned(),
+                role: PeerRole::Answerer,
+            }));
+        }
+    }
+
+    /// a player in the room, with their seat index
+    fn player(&self, id: &str) -> Option<Player> {
+        let p = self.players.get(id)?;
+        Some(Player {
+            id: p.id.clone(),
+            c: p.c,
+            i: self.seat_of(id),
+            ready: p.ready,
+            spectator: p.spectator,
+        })
+    }
+
+    /// a player's own view of themselves, including their resume token
+    fn you(&self, id: &str) -> Option<You> {
+        let p = self.players.get(id)?;
+        Some(You {
+            id: p.id.clone(),
+            c: p.c,
+            i: self.seat_of(id),
+            token: p.token.clone(),
+            spectator: p.spectator,
+        })
+    }
+
+    /// a short overview of the room, for listings
+    fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            code: self.code.clone(),
+            players: self.seated(),
+            open: self.max_players.saturating_sub(self.seated()),
+            created_at: self
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            in_match: self.is_locked(),
+        }
+    }
 }
 
 /// `RoomServer` manages game rooms
-#[derive(Default)]
 pub struct RoomServer {
     /// map of rooms, each with set of users
     rooms: HashMap<String, Room>,
+    /// the IP address of each connected session, where known
+    ips: HashMap<Addr<session::PlayerSession>, String>,
     rng: ThreadRng,
+    /// the scheme new player IDs are generated with
+    ids: Box<dyn IdScheme>,
+    config: RoomConfig,
 }
 
-impl RoomServer {}
+impl Default for RoomServer {
+    fn default() -> Self {
+        Self::new(RoomConfig::default())
+    }
+}
+
+impl RoomServer {
+    pub fn new(config: RoomConfig) -> Self {
+        Self {
+            rooms: HashMap::new(),
+            ips: HashMap::new(),
+            rng: rand::thread_rng(),
+            ids: Box::new(AlphanumericIds::default()),
+            config,
+        }
+    }
+
+    /// allocate a player ID that no other player on the server is using
+    fn allocate_player_id(&mut self) -> String {
+        loop {
+            let id = self.ids.generate();
+            if !self
+                .rooms
+                .values()
+                .any(|room| room.players.contains_key(&id))
+            {
+                return id;
+            }
+        }
+    }
+
+    /// generate a secret token for resuming a session
+    fn issue_token(&mut self) -> String {
+        (&mut self.rng)
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    /// generate a room code that is not in use and contains no blocked words.
+    /// Returns `None` if no such code was found, e.g. when the code space is
+    /// nearly exhausted
+    fn generate_code(&mut self) -> Option<String> {
+        for _ in 0..CODE_ATTEMPTS {
+            let code: String = (0..self.config.code_length)
+                .filter_map(|_| self.config.code_alphabet.choose(&mut self.rng))
+                .collect();
+            if !self.rooms.contains_key(&code) && !self.config.is_blocked_code(&code) {
+                return Some(code);
+            }
+        }
+        None
+    }
+
+    /// add a new player to a room, telling them and the other players about it.
+    /// Players without a seat join as spectators
+    fn add_player(&mut self, code: &str, seat: Option<usize>, addr: Addr<session::PlayerSession>) {
+        //player ID
+        let pid = self.allocate_player_id();
+        let token = self.issue_token();
+        let ip = self.ips.get(&addr).cloned();
+        let Some(room) = self.rooms.get_mut(code) else {
+            return;
+        };
+        room.emptied_at = None;
+        if let Some(seat) = seat {
+            room.take_seat(seat, pid.clone());
+        }
+
+        log::info!(
+            "Player {} joined room {} ({} players already in room)",
+            pid,
+            code,
+            room.players.len()
+        );
+
+        //insert the user into the room
+        room.players.insert(
+            pid.clone(),
+            PlayerInfo {
+                id: pid.clone(),
+                c: None,
+                ready: false,
+                spectator: seat.is_none(),
+                addr: addr.clone(),
+                ip,
+                token,
+                disconnected: None,
+            },
+        );
+
+        //inform each player in the room about the new player
+        if let Some(player) = room.player(&pid) {
+            room.broadcast_except(&pid, Response::Player(player));
+        }
+        if seat.is_some() {
+            room.cancel_countdown();
+        }
+
+        if let Some(you) = room.you(&pid) {
+            addr.do_send(Response::You(you));
+        }
+        // the first player in becomes the host
+        if room.host.is_none() && seat.is_some() {
+            room.set_host(pid.clone());
+        }
+        //broadcast to the new user the info about the other users in the room
+        addr.do_send(Response::RoomInfo(room.info()));
+        room.connect(&pid);
+    }
+
+    /// create a room and make the player its host. `cmd` is the request that
+    /// asked for the room, for error reporting
+    fn create_room(
+        &mut self,
+        addr: Addr<session::PlayerSession>,
+        max_players: Option<usize>,
+        settings: RoomSettings,
+        cmd: &str,
+    ) {
+        if let Err(message) = self.config.validate_settings(&settings) {
+            addr.do_send(Response::error(
+                ErrorCode::InvalidSettings,
+                Some(cmd),
+                message,
+            ));
+            return;
+        }
+        let Some(code) = self.generate_code() else {
+            log::error!("could not find a free room code");
+            // tell the player that no room was created
+            addr.do_send(Response::error(
+                ErrorCode::RoomCreationFailed,
+                Some(cmd),
+                "no room codes are available, try again later",
+            ));
+            return;
+        };
+        // a session may only be in one room at a time
+        self.leave_current_room(&addr);
+
+        let max_players = self.config.room_size(max_players);
+        self.rooms
+            .insert(code.clone(), Room::new(code.clone(), max_players, settings));
+        self.add_player(&code, Some(0), addr);
+    }
+
+    /// the room a host-only request is for. Tells the player when they are not
+    /// the host of the room
+    fn hosted_room(&mut self, code: &str, id: &str, cmd: &str) -> Option<&mut Room> {
+        let room = self.rooms.get_mut(code)?;
+        if room.host.as_deref() == Some(id) {
+            return Some(room);
+        }
+        if let Some(p) = room.players.get(id) {
+            p.addr.do_send(Response::error(
+                ErrorCode::NotHost,
+                Some(cmd),
+                "only the host can do that",
+            ));
+        }
+        None
+    }
+
+    /// remove a player from a room and let the remaining players know
+    fn remove_player(&mut self, code: &str, id: &str, reason: LeaveReason) {
+        let Some(room) = self.rooms.get_mut(code) else {
+            return;
+        };
+        if room.players.remove(id).is_none() {
+            // the player already left this room
+            return;
+        }
+        log::info!("Player {} left room {} ({:?})", id, code, reason);
+        // free up the player's seat, leaving everyone else where they are
+        let index = room.seat_of(id);
+        if let Some(idx) = index {
+            room.players_order[idx] = None;
+        }
+        // inform each player in the room about the disconnected player
+        room.broadcast(Response::PlayerLeft(PlayerLeft {
+            id: id.to_owned(),
+            i: index,
+            reason,
+            message: None,
+        }));
+        if index.is_some() {
+            room.cancel_countdown();
+        }
+        if room.host.as_deref() == Some(id) {
+            room.migrate_host(index.unwrap_or_default());
+        }
+        // a match nobody is playing in is over, so spectators can take the seats
+        if room.state == RoomState::InMatch && room.seated() == 0 {
+            room.set_state(RoomState::PostMatch, None);
+        }
+        if room.players.is_empty() {
+            if self.config.empty_room_grace.is_zero() {
+                log::info!("removing empty room {}", code);
+                self.rooms.remove(code);
+            } else {
+                room.emptied_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// remove the session from whichever room it is in, if any
+    fn leave_current_room(&mut self, addr: &Addr<session::PlayerSession>) {
+        let current = self.rooms.iter().find_map(|(code, room)| {
+            room.players
+                .values()
+                .find(|p| &p.addr == addr)
+                .map(|p| (code.clone(), p.id.clone()))
+        });
+        if let Some((code, id)) = current {
+            self.remove_player(&code, &id, LeaveReason::Left);
+        }
+    }
+
+    /// start the match once the countdown is over, unless the room changed
+    /// state in the meantime
+    fn begin_match(&mut self, code: &str, generation: u64) {
+        if let Some(room) = self.rooms.get_mut(code) {
+            if room.state == RoomState::Countdown && room.generation == generation {
+                room.set_state(RoomState::InMatch, None);
+            }
+        }
+    }
+
+    /// remove players whose resume window has passed, and every room that has
+    /// been empty for longer than the grace period
+    fn sweep(&mut self) {
+        let window = self.config.resume_window;
+        let expired: Vec<(String, String, LeaveReason)> = self
+            .rooms
+            .iter()
+            .flat_map(|(code, room)| {
+                room.players.values().filter_map(|p| match p.disconnected {
+                    Some((at, reason)) if at.elapsed() >= window => {
+                        Some((code.clone(), p.id.clone(), reason))
+                    }
+                    _ => None,
+                })
+            })
+            .collect();
+        for (code, id, reason) in expired {
+            self.remove_player(&code, &id, reason);
+        }
+
+        let grace = self.config.empty_room_grace;
+        self.rooms.retain(|code, room| match room.emptied_at {
+            Some(emptied_at) if emptied_at.elapsed() >= grace => {
+                log::info!("removing empty room {}", code);
+                false
+            }
+            _ => true,
+        });
+    }
+}
 
 /// Make actor from `RoomServer`
 impl Actor for RoomServer {
     /// We are going to use simple Context, we just need ability to communicate
     /// with other actors.
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // periodically clean up rooms nobody came back to
+        ctx.run_interval(self.config.sweep_interval, |act, _| act.sweep());
+    }
+}
+
+/// Handler for Connect message.
+impl Handler<Connect> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) {
+        let config = ice::ice_config(&self.config, SystemTime::now());
+        msg.addr.do_send(Response::IceConfig(config));
+        if let Some(ip) = msg.ip {
+            self.ips.insert(msg.addr, ip);
+        }
+    }
 }
 
 /// Handler for Disconnect message.
@@ -116,28 +893,134 @@ impl Handler<Disconnect> for RoomServer {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        if let (Some(code), Some(id)) = (msg.room, msg.id) {
-            log::info!("Player {} left room {}", id, code);
-            if let Some(room) = self.rooms.get_mut(&code) {
-                room.players.remove(&id);
-                let index = room
-                    .players_order
-                    .iter()
-                    .position(|x| x.as_ref() == Some(&id));
-                if let Some(idx) = index {
-                    room.players_order.remove(idx);
-                }
-                // inform each player in the room about the disconnected player
-                // This can be in the form of a `Player` message- with their ID but a player index of -1
-                for (id, p) in room.players.clone() {
-                    p.addr.do_send(responses::Response::Player(Player {
-                        id,
-                        c: None,
-                        i: Some(usize::MAX),
-                    }));
-                }
-            }
+        let Disconnect {
+            addr,
+            id,
+            room,
+            reason,
+        } = msg;
+        self.ips.remove(&addr);
+        let (Some(code), Some(id)) = (room, id) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        let Some(player) = room.players.get_mut(&id) else {
+            return;
+        };
+        // ignore sessions that were replaced by a resumed one, or already dropped
+        if player.addr != addr || player.disconnected.is_some() {
+            return;
+        }
+        if self.config.resume_window.is_zero() {
+            self.remove_player(&code, &id, reason);
+            return;
+        }
+        // hold the player's seat so they can resume their session
+        log::info!(
+            "Player {} disconnected from room {} ({:?})",
+            id,
+            code,
+            reason
+        );
+        player.disconnected = Some((Instant::now(), reason));
+        let i = room.seat_of(&id);
+        room.broadcast(Response::Disconnected(PlayerStatus { id: id.clone(), i }));
+        if i.is_some() {
+            room.cancel_countdown();
+        }
+        // the host has to be connected to run the match
+        if room.host.as_deref() == Some(id.as_str()) {
+            room.migrate_host(i.unwrap_or_default());
+        }
+    }
+}
+
+/// Handler for LeaveRoom message.
+impl Handler<LeaveRoom> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: LeaveRoom, _: &mut Context<Self>) {
+        let is_member = self
+            .rooms
+            .get(&msg.room)
+            .and_then(|room| room.players.get(&msg.id))
+            .is_some_and(|p| p.addr == msg.addr);
+        if is_member {
+            self.remove_player(&msg.room, &msg.id, LeaveReason::Left);
+        }
+    }
+}
+
+/// Reclaim a seat with a resume token
+impl Handler<ResumeSession> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResumeSession, _: &mut Context<Self>) {
+        let ResumeSession { addr, token } = msg;
+        let found = self.rooms.iter().find_map(|(code, room)| {
+            room.players
+                .values()
+                .find(|p| p.token == token)
+                .map(|p| (code.clone(), p.id.clone(), p.addr == addr))
+        });
+        let Some((code, id, same_session)) = found else {
+            addr.do_send(Response::error(
+                ErrorCode::ResumeFailed,
+                Some("resume"),
+                "the session has expired",
+            ));
+            return;
+        };
+        // a session may only be in one room at a time
+        if !same_session {
+            self.leave_current_room(&addr);
+        }
+
+        // hand out a fresh token, so the old one cannot be used again
+        let token = self.issue_token();
+        let ip = self.ips.get(&addr).cloned();
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        let seat = room.seat_of(&id);
+        let Some(player) = room.players.get_mut(&id) else {
+            return;
+        };
+        // the old connection, if it is still open, no longer speaks for the player
+        if player.addr != addr {
+            player.addr.do_send(Response::PlayerLeft(PlayerLeft {
+                id: id.clone(),
+                i: seat,
+                reason: LeaveReason::Resumed,
+                message: Some("your session was resumed from another connection".to_owned()),
+            }));
+        }
+        player.addr = addr.clone();
+        if ip.is_some() {
+            player.ip = ip;
+        }
+        player.token = token;
+        if player.disconnected.take().is_some() {
+            log::info!("Player {} reconnected to room {}", id, code);
+            let status = PlayerStatus {
+                id: id.clone(),
+                i: room.seat_of(&id),
+            };
+            room.broadcast_except(&id, Response::Reconnected(status));
+        }
+        // a room whose players all dropped is left without a host
+        if room.host.is_none() && room.seat_of(&id).is_some() {
+            room.set_host(id.clone());
+        }
+
+        if let Some(you) = room.you(&id) {
+            addr.do_send(Response::You(you));
         }
+        addr.do_send(Response::RoomInfo(room.info()));
+        // the new session has none of the old one's connections
+        room.connect(&id);
     }
 }
 
@@ -147,136 +1030,550 @@ impl Handler<JoinRoom> for RoomServer {
 
     fn handle(&mut self, msg: JoinRoom, _: &mut Context<Self>) {
         let JoinRoom { addr, code } = msg;
-        if self.rooms.get_mut(&code).is_none() {
+        let Some(room) = self.rooms.get(&code) else {
             // tell the connecting player that they did not join a room successfully
-            addr.do_send(responses::Response::RoomInfo(RoomInfo {
-                players: [].to_vec(),
-                code: "".to_string(),
-            }));
+            addr.do_send(Response::error(
+                ErrorCode::RoomNotFound,
+                Some("join"),
+                format!("room {} does not exist", code),
+            ));
             //exit the function early
-            return ();
+            return;
+        };
+        log::info!("found room {} ({} players)", room.code, room.players.len());
+
+        // joining the room the player is already in just resends its state
+        if let Some(you) = room
+            .players
+            .values()
+            .find(|p| p.addr == addr)
+            .and_then(|p| room.you(&p.id))
+        {
+            addr.do_send(Response::You(you));
+            addr.do_send(Response::RoomInfo(room.info()));
+            return;
+        }
+        let banned = self
+            .ips
+            .get(&addr)
+            .is_some_and(|ip| room.banned.contains(ip));
+        if banned {
+            addr.do_send(Response::error(
+                ErrorCode::Banned,
+                Some("join"),
+                format!("you are banned from room {}", code),
+            ));
+            return;
         }
+        // once the match started, new players can only watch
+        if room.is_locked() {
+            if !self.config.allow_spectators {
+                addr.do_send(Response::error(
+                    ErrorCode::MatchInProgress,
+                    Some("join"),
+                    format!("room {} is already playing a match", code),
+                ));
+                return;
+            }
+            self.leave_current_room(&addr);
+            self.add_player(&code, None, addr);
+            return;
+        }
+        let Some(pos) = room.free_seat() else {
+            addr.do_send(Response::error(
+                ErrorCode::RoomFull,
+                Some("join"),
+                format!("room {} is full", code),
+            ));
+            return;
+        };
 
-        if let Some(room) = self.rooms.get_mut(&code) {
-            log::info!("found room {:#?}", room);
-            // add the player to the last slot of the room by default
-            let mut pos = room.players_order.len();
-            // Find the position of the first empty space in the vec
-            if let Some(empty_pos) = room.players_order.iter().position(|x| x.is_none()) {
-                pos = empty_pos;
+        // a session may only be in one room at a time
+        self.leave_current_room(&addr);
+
+        // seat the player in the lowest free seat
+        self.add_player(&code, Some(pos), addr);
+    }
+}
+
+impl Handler<CreateRoom> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: CreateRoom, _ctx: &mut Context<Self>) {
+        let CreateRoom {
+            addr,
+            max_players,
+            settings,
+        } = msg;
+        self.create_room(addr, max_players, settings.unwrap_or_default(), "create");
+    }
+}
+
+/// put the player in the fullest public room that is still in its lobby,
+/// creating a new public room when none has a free seat
+impl Handler<QuickMatch> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: QuickMatch, _: &mut Context<Self>) {
+        let QuickMatch { addr } = msg;
+        let ip = self.ips.get(&addr);
+        let found = self
+            .rooms
+            .values()
+            .filter(|room| room.settings.public && room.state == RoomState::Lobby)
+            .filter(|room| !room.players.values().any(|p| p.addr == addr))
+            .filter(|room| !ip.is_some_and(|ip| room.banned.contains(ip)))
+            .filter_map(|room| Some((room, room.free_seat()?)))
+            // the fullest room wins, and the oldest among equally full ones
+            .max_by_key(|(room, _)| (room.seated(), Reverse(room.created_at)))
+            .map(|(room, seat)| (room.code.clone(), seat));
+        match found {
+            Some((code, seat)) => {
+                // a session may only be in one room at a time
+                self.leave_current_room(&addr);
+                self.add_player(&code, Some(seat), addr);
+            }
+            None => {
+                let settings = RoomSettings {
+                    public: true,
+                    ..Default::default()
+                };
+                self.create_room(addr, None, settings, "quickmatch");
             }
+        }
+    }
+}
 
-            //player ID
-            let pid: String = (&mut self.rng)
-                .sample_iter(&Alphanumeric)
-                .take(8)
-                .map(char::from)
-                .collect();
-            //TODO repeat the above process if the ID is not unique in the room
-            addr.do_send(responses::Response::You(Player {
-                id: pid.to_owned(),
-                c: None,
-                i: Some(pos),
-            }));
+/// move a player to a free seat and share the new seating with the room
+impl Handler<ChooseSeat> for RoomServer {
+    type Result = ();
 
-            // Insert the id into the vec at that position
-            room.players_order.insert(pos, Some(pid.clone()));
+    fn handle(&mut self, msg: ChooseSeat, _: &mut Context<Self>) {
+        let ChooseSeat { id, room, seat } = msg;
+        let unique_characters = self.config.unique_characters;
+        let Some(room) = self.rooms.get_mut(&room) else {
+            return;
+        };
+        let Some(player) = room.players.get(&id) else {
+            return;
+        };
+        let addr = player.addr.clone();
+        // seats are the player slots of the match, so they only change between matches
+        if room.is_locked() {
+            addr.do_send(Response::error(
+                ErrorCode::InvalidState,
+                Some("seat"),
+                "seats can only be changed between matches",
+            ));
+            return;
+        }
+        let taken = room.players_order.get(seat).is_some_and(Option::is_some);
+        if seat >= room.max_players || taken {
+            addr.do_send(Response::error(
+                ErrorCode::SeatUnavailable,
+                Some("seat"),
+                format!("seat {} is not available", seat),
+            ));
+            return;
+        }
+        if let Some(current) = room.seat_of(&id) {
+            room.players_order[current] = None;
+        }
+        let was_in_mesh = room.in_mesh(&id);
+        room.take_seat(seat, id.clone());
+        // spectators' choices were not checked against the seated players
+        let taken = |c: Option<u8>| {
+            room.players
+                .values()
+                .any(|p| p.id != id && !p.spectator && c.is_some() && p.c == c)
+        };
+        let clear_choice = unique_characters
+            && room
+                .players
+                .get(&id)
+                .is_some_and(|p| p.spectator && taken(p.c));
+        if let Some(player) = room.players.get_mut(&id) {
+            player.spectator = false;
+            if clear_choice {
+                player.c = None;
+            }
+        }
+        // a spectator taking a seat in a room without seated players takes over
+        if room.host.is_none() {
+            room.set_host(id.clone());
+        }
+        room.broadcast(Response::RoomInfo(room.info()));
+        if !was_in_mesh {
+            room.connect(&id);
+        }
+    }
+}
 
-            //create a new player
-            let new_player = PlayerInfo {
-                id: pid.clone(),
-                c: None,
-                addr: addr.clone(),
+/// hand the host role to another connected player
+impl Handler<TransferHost> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: TransferHost, _: &mut Context<Self>) {
+        let TransferHost { id, room, to } = msg;
+        let Some(room) = self.hosted_room(&room, &id, "host") else {
+            return;
+        };
+        // the host plays the match, so spectators cannot take it over
+        let problem = if !room.is_connected(&to) {
+            Some(format!("player {} is not in the room", to))
+        } else if room.seat_of(&to).is_none() {
+            Some(format!("player {} is spectating and cannot host", to))
+        } else {
+            None
+        };
+        if let Some(message) = problem {
+            if let Some(p) = room.players.get(&id) {
+                p.addr.do_send(Response::error(
+                    ErrorCode::PlayerNotFound,
+                    Some("host"),
+                    message,
+                ));
+            }
+            return;
+        }
+        room.hand_over_host(to);
+    }
+}
+
+/// remove a player from the room at the host's request
+impl Handler<KickPlayer> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: KickPlayer, _: &mut Context<Self>) {
+        let KickPlayer {
+            id,
+            room: code,
+            target,
+            reason,
+            ban,
+        } = msg;
+        let cmd = if ban { "ban" } else { "kick" };
+        let Some(room) = self.hosted_room(&code, &id, cmd) else {
+            return;
+        };
+        let Some((addr, ip)) = room
+            .players
+            .get(&target)
+            .filter(|_| target != id)
+            .map(|p| (p.addr.clone(), p.ip.clone()))
+        else {
+            if let Some(p) = room.players.get(&id) {
+                p.addr.do_send(Response::error(
+                    ErrorCode::PlayerNotFound,
+                    Some(cmd),
+                    format!("player {} cannot be removed", target),
+                ));
+            }
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        let leave_reason = if ban {
+            let Some(ip) = ip else {
+                log::warn!("no IP address to ban player {} with", target);
+                if let Some(p) = room.players.get(&id) {
+                    p.addr.do_send(Response::error(
+                        ErrorCode::BanFailed,
+                        Some(cmd),
+                        format!("the address of player {} is unknown", target),
+                    ));
+                }
+                return;
             };
-            //inform each player in the room about the new player
-            for (_id, p) in room.players.clone() {
-                p.addr.do_send(responses::Response::Player(Player {
-                    id: new_player.id.clone(),
-                    c: new_player.c,
-                    i: Some(pos),
-                }));
+            room.banned.insert(ip);
+            LeaveReason::Banned
+        } else {
+            LeaveReason::Kicked
+        };
+        // let the player know why they were removed
+        addr.do_send(Response::PlayerLeft(PlayerLeft {
+            id: target.clone(),
+            i: room.seat_of(&target),
+            reason: leave_reason,
+            message: reason,
+        }));
+        self.remove_player(&code, &target, leave_reason);
+    }
+}
+
+/// toggle whether a player is ready to start the match
+impl Handler<SetReady> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetReady, _: &mut Context<Self>) {
+        let SetReady { id, room, ready } = msg;
+        let Some(room) = self.rooms.get_mut(&room) else {
+            return;
+        };
+        let state = room.state;
+        let Some(player) = room.players.get_mut(&id) else {
+            return;
+        };
+        if !matches!(state, RoomState::Lobby | RoomState::Countdown) {
+            player.addr.do_send(Response::error(
+                ErrorCode::InvalidState,
+                Some("ready"),
+                "the match has already started",
+            ));
+            return;
+        }
+        if player.spectator {
+            player.addr.do_send(Response::error(
+                ErrorCode::InvalidState,
+                Some("ready"),
+                "take a seat to play in the match",
+            ));
+            return;
+        }
+        player.ready = ready;
+        room.broadcast(Response::Ready(Ready { id, ready }));
+        if !ready {
+            room.cancel_countdown();
+        }
+    }
+}
+
+/// move a room through its lifecycle: lobby, countdown, match and post-match
+impl Handler<ChangeState> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChangeState, ctx: &mut Context<Self>) {
+        let ChangeState { id, room, state } = msg;
+        let countdown = self.config.match_countdown;
+        let (cmd, allowed_from): (&str, &[RoomState]) = match state {
+            RoomState::Countdown | RoomState::InMatch => ("start", &[RoomState::Lobby]),
+            RoomState::PostMatch => ("end", &[RoomState::InMatch]),
+            RoomState::Lobby => ("lobby", &[RoomState::InMatch, RoomState::PostMatch]),
+        };
+        let Some(room) = self.hosted_room(&room, &id, cmd) else {
+            return;
+        };
+        let error = |code, message: &str| {
+            if let Some(p) = room.players.get(&id) {
+                p.addr.do_send(Response::error(code, Some(cmd), message));
             }
+        };
+        if !allowed_from.contains(&room.state) {
+            error(
+                ErrorCode::InvalidState,
+                &format!("cannot {} while in {:?}", cmd, room.state),
+            );
+            return;
+        }
+        match state {
+            RoomState::Countdown | RoomState::InMatch => {
+                let everyone_ready = room
+                    .players
+                    .values()
+                    .filter(|p| !p.spectator)
+                    .all(|p| p.ready && p.c.is_some());
+                if !everyone_ready {
+                    error(
+                        ErrorCode::NotReady,
+                        "every player has to choose a character and be ready",
+                    );
+                    return;
+                }
+                if countdown.is_zero() {
+                    room.set_state(RoomState::InMatch, None);
+                } else {
+                    room.set_state(RoomState::Countdown, Some(countdown));
+                    let code = room.code.clone();
+                    let generation = room.generation;
+                    ctx.run_later(countdown, move |act, _| act.begin_match(&code, generation));
+                }
+            }
+            RoomState::PostMatch => room.set_state(RoomState::PostMatch, None),
+            RoomState::Lobby => {
+                // everyone has to ready up again for the next match
+                for p in room.players.values_mut() {
+                    p.ready = false;
+                }
+                room.set_state(RoomState::Lobby, None);
+                room.broadcast(Response::RoomInfo(room.info()));
+            }
+        }
+    }
+}
 
-            log::info!(
-                "Player {} joined room {} ({} players already in room)",
-                pid,
-                code,
-                room.players.len()
+/// store a player's character choice and share it with the room
+impl Handler<ChooseCharacter> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChooseCharacter, _: &mut Context<Self>) {
+        let ChooseCharacter { id, room, c } = msg;
+        let Some(room) = self.rooms.get_mut(&room) else {
+            return;
+        };
+        let Some(addr) = room.players.get(&id).map(|p| p.addr.clone()) else {
+            return;
+        };
+        let error = |code, message: String| {
+            addr.do_send(Response::error(code, Some("choice"), message));
+        };
+        if room.state != RoomState::Lobby {
+            error(
+                ErrorCode::InvalidState,
+                "characters can only be chosen in the lobby".to_owned(),
+            );
+            return;
+        }
+        if c >= self.config.roster_size {
+            error(
+                ErrorCode::InvalidCharacter,
+                format!("character {} does not exist", c),
             );
+            return;
+        }
+        let taken = room
+            .players
+            .values()
+            .any(|p| p.id != id && !p.spectator && p.c == Some(c));
+        if self.config.unique_characters && taken {
+            error(
+                ErrorCode::CharacterTaken,
+                format!("character {} was already chosen", c),
+            );
+            return;
+        }
+        if let Some(player) = room.players.get_mut(&id) {
+            player.c = Some(c);
+        }
+        // the chooser gets their accepted choice back along with everyone else
+        if let Some(player) = room.player(&id) {
+            room.broadcast(Response::Player(player));
+        }
+    }
+}
 
-            //insert the user into the room
-            room.players.insert(pid.clone(), new_player);
-
-            //broadcast to the new user the info about the other users in the room
-            addr.do_send(responses::Response::RoomInfo(RoomInfo {
-                players: room
-                    .players_order
-                    .iter()
-                    .enumerate()
-                    .map(|(index, item)| {
-                        item.as_ref().and_then(|key| {
-                            room.players.get(key).map(|p| Player {
-                                id: p.id.clone(),
-                                i: Some(index),
-                                c: p.c,
-                            })
-                        })
-                    })
-                    .collect(),
-                code: code.clone(),
-            }));
+/// seat a matched group of players together in a new room
+impl Handler<CreateMatch> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: CreateMatch, _: &mut Context<Self>) {
+        let CreateMatch { mut members, mode } = msg;
+        // players may have dropped while the match was being formed
+        members.retain(Addr::connected);
+        if members.is_empty() {
+            return;
+        }
+        let Some(code) = self.generate_code() else {
+            log::error!("could not find a free room code");
+            for addr in &members {
+                addr.do_send(Response::error(
+                    ErrorCode::RoomCreationFailed,
+                    Some("queue"),
+                    "no room codes are available, try again later",
+                ));
+            }
+            return;
+        };
+        let room = Room::new(code.clone(), members.len(), RoomSettings::for_mode(mode));
+        self.rooms.insert(code.clone(), room);
+        for (seat, addr) in members.into_iter().enumerate() {
+            // a session may only be in one room at a time
+            self.leave_current_room(&addr);
+            self.add_player(&code, Some(seat), addr);
         }
     }
 }
 
-impl Handler<CreateRoom> for RoomServer {
+/// replace a room's settings and share them with every player
+impl Handler<ChangeSettings> for RoomServer {
     type Result = ();
 
-    fn handle(&mut self, msg: CreateRoom, _ctx: &mut Context<Self>) {
-        let addr = msg.addr;
-        let rng = &mut self.rng;
-        // generate a random 4-letter code
-        let code: String = rng
-            .sample_iter(&Alphanumeric)
-            .map(char::from)
-            .filter(|c| c.is_alphabetic() && c.is_lowercase())
-            .take(4)
-            .collect();
+    fn handle(&mut self, msg: ChangeSettings, _: &mut Context<Self>) {
+        let ChangeSettings {
+            id,
+            room: code,
+            changes,
+        } = msg;
+        let Some(room) = self.hosted_room(&code, &id, "settings") else {
+            return;
+        };
+        let Some(addr) = room.players.get(&id).map(|p| p.addr.clone()) else {
+            return;
+        };
+        if room.state != RoomState::Lobby {
+            addr.do_send(Response::error(
+                ErrorCode::InvalidState,
+                Some("settings"),
+                "settings can only be changed in the lobby",
+            ));
+            return;
+        }
+        let settings = changes.apply(&room.settings);
+        if let Err(message) = self.config.validate_settings(&settings) {
+            addr.do_send(Response::error(
+                ErrorCode::InvalidSettings,
+                Some("settings"),
+                message,
+            ));
+            return;
+        }
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        room.settings = settings;
+        room.broadcast(Response::RoomInfo(room.info()));
+    }
+}
 
-        let pid: String = rng
-            .sample_iter(&Alphanumeric)
-            .take(8)
-            .map(char::from)
+/// let spectators into the room's WebRTC mesh, or keep them out of it
+impl Handler<SetSpectatorMesh> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetSpectatorMesh, _: &mut Context<Self>) {
+        let SetSpectatorMesh { id, room, enabled } = msg;
+        let Some(room) = self.hosted_room(&room, &id, "spectators") else {
+            return;
+        };
+        let joins_mesh = enabled && !room.spectator_mesh;
+        room.spectator_mesh = enabled;
+        room.broadcast(Response::RoomInfo(room.info()));
+        if joins_mesh {
+            let spectators: Vec<String> = room
+                .players
+                .values()
+                .filter(|p| p.spectator)
+                .map(|p| p.id.clone())
+                .collect();
+            // each spectator only offers to the peers already in the mesh,
+            // and answers the spectators after it
+            for (n, spectator) in spectators.iter().enumerate() {
+                room.connect_skipping(spectator, &spectators[n + 1..]);
+            }
+        }
+    }
+}
+
+/// list every room, or every public room, oldest first
+impl Handler<ListRooms> for RoomServer {
+    type Result = MessageResult<ListRooms>;
+
+    fn handle(&mut self, msg: ListRooms, _: &mut Context<Self>) -> Self::Result {
+        let mut rooms: Vec<&Room> = self
+            .rooms
+            .values()
+            .filter(|room| !msg.public_only || room.settings.public)
             .collect();
-        let you = Player {
-            id: pid.to_owned(),
-            c: None,
-            i: Some(0),
-        };
-
-        self.rooms.insert(
-            code.clone(),
-            Room {
-                code: code.clone(),
-                players: [(
-                    pid.clone(),
-                    PlayerInfo {
-                        id: pid.clone(),
-                        addr: addr.clone(),
-                        c: None,
-                    },
-                )]
-                .iter()
-                .cloned()
-                .collect(),
-                players_order: [Some(pid)].to_vec(),
-            },
-        );
-        addr.do_send(responses::Response::You(you.clone()));
-        addr.do_send(responses::Response::RoomInfo(RoomInfo {
-            players: [Some(you)].to_vec(),
-            code,
-        }));
+        rooms.sort_by_key(|room| room.created_at);
+        MessageResult(rooms.into_iter().map(Room::summary).collect())
+    }
+}
+
+/// look up a single room by its code
+impl Handler<GetRoom> for RoomServer {
+    type Result = Option<RoomInfo>;
+
+    fn handle(&mut self, msg: GetRoom, _: &mut Context<Self>) -> Self::Result {
+        self.rooms.get(&msg.code).map(Room::info)
     }
 }
 
@@ -292,7 +1589,7 @@ impl Handler<Broadcast> for RoomServer {
                 }
             }
         } else {
-            for (_code, room) in &self.rooms {
+            for room in self.rooms.values() {
                 for (_id, p) in room.players.clone() {
                     p.addr.do_send(responses::Response::Alert(responses::Chat {
                         msg: msg.text.clone(),
@@ -309,7 +1606,10 @@ impl Handler<ToRoom> for RoomServer {
 
     fn handle(&mut self, msg: ToRoom, _ctx: &mut Self::Context) {
         if let Some(room) = self.rooms.get(&msg.room) {
-            for (player_id, player) in room.players.iter() {
+            if !room.in_mesh(&msg.id) {
+                return;
+            }
+            for (player_id, player) in room.players.iter().filter(|(id, _)| room.in_mesh(id)) {
                 // check if the player ID matches the ID provided in msg
                 if player_id.ne(&msg.id) {
                     log::info!(
@@ -325,3 +1625,80 @@ impl Handler<ToRoom> for RoomServer {
         }
     }
 }
+
+/// forward a message to a single member of the sender's room
+impl Handler<ToPlayer> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: ToPlayer, _: &mut Self::Context) {
+        let ToPlayer {
+            msg,
+            room,
+            id,
+            to,
+            cmd,
+        } = msg;
+        let Some(room) = self.rooms.get(&room) else {
+            return;
+        };
+        let Some(sender) = room.players.get(&id) else {
+            return;
+        };
+        if !room.in_mesh(&id) {
+            return;
+        }
+        match room
+            .players
+            .get(&to)
+            .filter(|p| p.disconnected.is_none() && room.in_mesh(&to) && to != id)
+        {
+            Some(player) => {
+                log::info!("sending message {:#?} from player {} to {}", msg, id, to);
+                player.addr.do_send(msg);
+            }
+            None => sender.addr.do_send(Response::error(
+                ErrorCode::PlayerNotFound,
+                Some(cmd),
+                format!("player {} is not connected to the room", to),
+            )),
+        }
+    }
+}
+
+/// forward a game frame to the player in a seat, or to everyone else in the room
+impl Handler<Relay> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Relay, _: &mut Self::Context) {
+        let Relay { id, room, to, data } = msg;
+        let Some(room) = self.rooms.get(&room) else {
+            return;
+        };
+        if !room.in_mesh(&id) {
+            return;
+        }
+        // players may only send frames for their own seat, so that they cannot
+        // drive anyone else
+        let sender = data.first().map(|&seat| usize::from(seat));
+        if sender.is_none() || sender != room.seat_of(&id) {
+            log::debug!("dropping frame from {} for another seat", id);
+            return;
+        }
+        let recipients: Vec<&PlayerInfo> = match to {
+            Some(seat) => room
+                .players_order
+                .get(seat)
+                .and_then(Option::as_ref)
+                .and_then(|target| room.players.get(target))
+                .into_iter()
+                .collect(),
+            None => room.players.values().collect(),
+        };
+        let recipients = recipients
+            .into_iter()
+            .filter(|p| p.id != id && p.disconnected.is_none() && room.in_mesh(&p.id));
+        for player in recipients {
+            player.addr.do_send(Frame(data.clone()));
+        }
+    }
+}
@@ -5,15 +5,17 @@ use std::time::{Duration, Instant};
 
 use actix::prelude::*;
 use actix_web_actors::ws;
-use serde::{Deserialize, Serialize};
 use serde_json;
 
-use crate::libs::socket::requests::Request;
+use crate::libs::config::RoomConfig;
+use crate::libs::socket::requests::{Kick, Request};
 
+use super::matchmaker::{self, Matchmaker};
 use super::server::{self, RoomServer};
 use super::{
-    responses::{self, Player},
-    server::ToRoom,
+    requests::{IceCandidate, Offer},
+    responses::{self, ErrorCode, LeaveReason, Response, RoomState},
+    server::{ToPlayer, ToRoom},
 };
 
 /// How often heartbeat pings are sent
@@ -22,10 +24,21 @@ const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// How long before lack of client response causes a timeout
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
-/// client-session messaging- main message structure received from the client
-#[derive(Message, Serialize, Deserialize, Clone, Debug)]
-#[rtype(result = "()")]
-pub struct Plain(pub String);
+/// The period over which client requests are counted for rate limiting
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// How many binary frames a client may have relayed per rate limiting window
+const RATE_LIMIT_FRAMES: u32 = 240;
+
+/// The largest binary frame that is relayed to other players
+const MAX_FRAME_SIZE: usize = 1024;
+
+/// The target slot in a binary frame's header that sends it to the whole room
+const BROADCAST_SLOT: u8 = u8::MAX;
+
+/// The smallest binary frame that is relayed: the target slot header and the
+/// sender's seat at the start of the payload
+const MIN_FRAME_SIZE: usize = 2;
 
 /// `PlayerSession` actor is responsible for tcp peer communications.
 pub struct PlayerSession {
@@ -33,11 +46,35 @@ pub struct PlayerSession {
     id: Option<String>,
     /// this is address of room hub
     hub: Addr<RoomServer>,
+    /// the matchmaking queue
+    matchmaker: Addr<Matchmaker>,
+    /// whether the player is waiting in the matchmaking queue
+    queued: bool,
     /// Client must send ping at least once per 10 seconds, otherwise we drop
     /// connection.
     hb: Instant,
     /// joined room
     room: Option<String>,
+    /// the client's IP address, if known
+    ip: Option<String>,
+    /// start of the current rate limiting window
+    window_start: Instant,
+    /// number of requests received in the current window
+    window_requests: u32,
+    /// number of signaling requests received in the current window
+    window_signaling: u32,
+    /// how many requests the client may send per window
+    request_limit: u32,
+    /// how many signaling requests the client may send per window
+    signaling_limit: u32,
+    /// number of binary frames received in the current window
+    window_frames: u32,
+}
+
+/// the `cmd` of a raw request, if it has one
+fn request_cmd(msg: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(msg).ok()?;
+    value.get("cmd")?.as_str().map(str::to_owned)
 }
 
 impl Actor for PlayerSession {
@@ -46,14 +83,21 @@ impl Actor for PlayerSession {
     fn started(&mut self, ctx: &mut Self::Context) {
         // we'll start heartbeat process on session start.
         self.hb(ctx);
+        self.hub.do_send(server::Connect {
+            addr: ctx.address(),
+            ip: self.ip.clone(),
+        });
     }
 
-    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+    fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
         log::info!("killing {:?}", self.id);
+        self.leave_queue(ctx);
         // notify hub/room server of disconnect
         self.hub.do_send(server::Disconnect {
+            addr: ctx.address(),
             id: self.id.clone(),
             room: self.room.clone(),
+            reason: LeaveReason::Left,
         });
         Running::Stop
     }
@@ -82,75 +126,60 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for PlayerSession {
             }
             ws::Message::Text(msg) => {
                 // Deserialize the binary data into a JSON object
-                if let Ok(json) = serde_json::from_str::<Request>(&msg) {
-                    match json {
-                        Request::PlayerChoice(p) => {
-                            if let (Some(id), Some(room)) = (self.id.clone(), self.room.clone()) {
-                                self.hub.do_send(ToRoom {
-                                    msg: responses::Response::Player(Player {
-                                        id: id.clone(),
-                                        c: Some(p.c),
-                                        i: None,
-                                    }),
-                                    id,
-                                    room,
-                                })
-                            }
-                        }
-                        Request::Join(j) => {
-                            let code = j.code;
-                            self.hub.do_send(server::JoinRoom {
-                                addr: ctx.address(),
-                                code: code.to_owned(),
-                            });
-                        }
-                        Request::Create(_) => {
-                            if let Some(_room) = &self.room {
-                                //TODO request existing room info from server
-                            } else {
-                                self.hub.do_send(server::CreateRoom {
-                                    addr: ctx.address(),
-                                });
-                            }
-                        }
-                        Request::Offer(offer) => {
-                            if let Some(room) = &self.room {
-                                if let Some(id) = &self.id {
-                                    self.hub.do_send(server::ToRoom {
-                                        msg: responses::Response::Offer(offer),
-                                        id: id.to_string(),
-                                        room: room.to_string(),
-                                    })
-                                }
-                            }
-                        }
-                        Request::Answer(offer) => {
-                            if let Some(room) = &self.room {
-                                if let Some(id) = &self.id {
-                                    self.hub.do_send(server::ToRoom {
-                                        msg: responses::Response::Answer(offer),
-                                        id: id.to_string(),
-                                        room: room.to_string(),
-                                    })
-                                }
-                            }
-                        }
-                        Request::IceCandidate(ice) => {
-                            if let Some(room) = &self.room {
-                                if let Some(id) = &self.id {
-                                    self.hub.do_send(server::ToRoom {
-                                        msg: responses::Response::IceCandidate(ice),
-                                        id: id.to_string(),
-                                        room: room.to_string(),
-                                    })
-                                }
-                            }
-                        }
+                let request = serde_json::from_str::<Request>(&msg);
+                let allowed = match &request {
+                    Ok(request) if request.is_signaling() => self.allow_signaling(),
+                    _ => self.allow_request(),
+                };
+                if !allowed {
+                    self.send(
+                        ctx,
+                        &Response::error(
+                            ErrorCode::RateLimited,
+                            request_cmd(&msg).as_deref(),
+                            "too many requests, slow down",
+                        ),
+                    );
+                    return;
+                }
+                match request {
+                    Ok(request) => self.handle_request(request, ctx),
+                    Err(e) => {
+                        log::debug!("Failed to deserialize message: {:#?}", msg);
+                        self.send(
+                            ctx,
+                            &Response::error(
+                                ErrorCode::MalformedRequest,
+                                request_cmd(&msg).as_deref(),
+                                e.to_string(),
+                            ),
+                        );
                     }
-                } else {
-                    log::debug!("Failed to deserialize message: {:#?}", msg)
                 }
             }
+            // game frames relayed through the server, when WebRTC fails. The
+            // first byte is the seat the frame is for, or `BROADCAST_SLOT`,
+            // and the payload after it starts with the sender's seat
+            ws::Message::Binary(mut data) => {
+                if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&data.len()) {
+                    log::debug!("dropping frame of {} bytes", data.len());
+                    return;
+                }
+                // frames are sent many times a second, so frames over the rate
+                // limit or from outside a room are dropped silently rather
+                // than answered with an error each
+                if !self.allow_frame() {
+                    return;
+                }
+                let (Some(id), Some(room)) = (self.id.clone(), self.room.clone()) else {
+                    return;
+                };
+                let to = match data.split_to(1)[0] {
+                    BROADCAST_SLOT => None,
+                    seat => Some(usize::from(seat)),
+                };
+                self.hub.do_send(server::Relay { id, room, to, data });
+            }
             _ => {
                 log::debug!("unhandled message: {:?}", msg);
             }
@@ -170,22 +199,310 @@ impl Handler<responses::Response> for PlayerSession {
             responses::Response::RoomInfo(room) => {
                 self.room = Some(room.code.clone());
             }
+            responses::Response::Queue(status) => {
+                self.queued = status.queued;
+            }
+            // TURN credentials stay valid for a while, so they are kept out of the log
+            responses::Response::IceConfig(config) => {
+                log::debug!("forwarding {} ICE servers", config.ice_servers.len());
+            }
+            // the player was removed from their room
+            responses::Response::PlayerLeft(left) if self.id.as_ref() == Some(&left.id) => {
+                self.id = None;
+                self.room = None;
+            }
             _ => log::debug!("forwarding message without modification: {:?}", msg),
         }
-        if let Ok(str) = serde_json::to_string(&msg) {
-            ctx.text(str);
-        }
+        self.send(ctx, &msg);
+    }
+}
+
+/// Handle frames relayed from other players, we send them to the peer as they are
+impl Handler<server::Frame> for PlayerSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: server::Frame, ctx: &mut Self::Context) {
+        ctx.binary(msg.0);
     }
 }
 
 /// Helper methods
 impl PlayerSession {
-    pub fn new(hub: Addr<RoomServer>) -> Self {
+    pub fn new(
+        hub: Addr<RoomServer>,
+        matchmaker: Addr<Matchmaker>,
+        ip: Option<String>,
+        config: &RoomConfig,
+    ) -> Self {
         Self {
             id: None,
             hub,
+            matchmaker,
+            queued: false,
             hb: Instant::now(),
             room: None,
+            ip,
+            window_start: Instant::now(),
+            window_requests: 0,
+            window_signaling: 0,
+            window_frames: 0,
+            request_limit: config.rate_limit_requests,
+            signaling_limit: config.rate_limit_signaling,
+        }
+    }
+
+    /// proxy a request from the client to the room server
+    fn handle_request(&mut self, request: Request, ctx: &mut ws::WebsocketContext<Self>) {
+        let cmd = request.cmd();
+        // picking a room by hand gives up the player's place in the queue
+        if matches!(
+            request,
+            Request::Join(_) | Request::Create(_) | Request::QuickMatch(_) | Request::Resume(_)
+        ) {
+            self.leave_queue(ctx);
+        }
+        match request {
+            Request::Join(j) => {
+                let code = j.code;
+                self.hub.do_send(server::JoinRoom {
+                    addr: ctx.address(),
+                    code: code.to_owned(),
+                });
+            }
+            // the server takes the player out of their current room, if any
+            Request::Create(create) => {
+                self.hub.do_send(server::CreateRoom {
+                    addr: ctx.address(),
+                    max_players: create.max_players,
+                    settings: create.settings,
+                });
+            }
+            // the server takes the player out of their current room, if any
+            Request::QuickMatch(_) => {
+                self.hub.do_send(server::QuickMatch {
+                    addr: ctx.address(),
+                });
+            }
+            Request::Queue(queue) => {
+                // marked right away, so leaving before the reply still dequeues
+                self.queued = true;
+                self.matchmaker.do_send(matchmaker::Enqueue {
+                    addr: ctx.address(),
+                    party_size: queue.party_size,
+                    mode: queue.mode,
+                    rating: queue.rating,
+                });
+            }
+            Request::Unqueue(_) => self.leave_queue(ctx),
+            Request::Leave(_) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.id = None;
+                    self.room = None;
+                    self.hub.do_send(server::LeaveRoom {
+                        addr: ctx.address(),
+                        id,
+                        room,
+                    });
+                }
+            }
+            // the server takes the player out of their current room, if any
+            Request::Resume(resume) => {
+                self.hub.do_send(server::ResumeSession {
+                    addr: ctx.address(),
+                    token: resume.token,
+                });
+            }
+            Request::Seat(seat) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub.do_send(server::ChooseSeat {
+                        id,
+                        room,
+                        seat: seat.i,
+                    });
+                }
+            }
+            Request::Host(host) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub.do_send(server::TransferHost {
+                        id,
+                        room,
+                        to: host.id,
+                    });
+                }
+            }
+            Request::Kick(kick) => self.kick(ctx, cmd, kick, false),
+            Request::Ban(kick) => self.kick(ctx, cmd, kick, true),
+            Request::Ready(ready) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub.do_send(server::SetReady {
+                        id,
+                        room,
+                        ready: ready.ready,
+                    });
+                }
+            }
+            Request::Start(_) => self.change_state(ctx, cmd, RoomState::Countdown),
+            Request::End(_) => self.change_state(ctx, cmd, RoomState::PostMatch),
+            Request::Lobby(_) => self.change_state(ctx, cmd, RoomState::Lobby),
+            Request::Spectators(spectators) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub.do_send(server::SetSpectatorMesh {
+                        id,
+                        room,
+                        enabled: spectators.mesh,
+                    });
+                }
+            }
+            Request::Settings(settings) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub.do_send(server::ChangeSettings {
+                        id,
+                        room,
+                        changes: settings.settings,
+                    });
+                }
+            }
+            Request::PlayerChoice(p) => {
+                if let Some((id, room)) = self.membership(ctx, cmd) {
+                    self.hub
+                        .do_send(server::ChooseCharacter { id, room, c: p.c });
+                }
+            }
+            Request::Offer(offer) => self.signal(ctx, cmd, offer.to.clone(), |id| {
+                Response::Offer(Offer {
+                    from: Some(id.to_owned()),
+                    ..offer
+                })
+            }),
+            Request::Answer(offer) => self.signal(ctx, cmd, offer.to.clone(), |id| {
+                Response::Answer(Offer {
+                    from: Some(id.to_owned()),
+                    ..offer
+                })
+            }),
+            Request::IceCandidate(ice) => self.signal(ctx, cmd, ice.to.clone(), |id| {
+                Response::IceCandidate(IceCandidate {
+                    from: Some(id.to_owned()),
+                    ..ice
+                })
+            }),
+        }
+    }
+
+    /// take the player out of the matchmaking queue, if they are in it
+    fn leave_queue(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.queued {
+            self.queued = false;
+            self.matchmaker.do_send(matchmaker::Dequeue {
+                addr: ctx.address(),
+            });
+        }
+    }
+
+    /// ask the server to remove another player from the room, banning them if
+    /// `ban` is set
+    fn kick(&self, ctx: &mut ws::WebsocketContext<Self>, cmd: &str, kick: Kick, ban: bool) {
+        if let Some((id, room)) = self.membership(ctx, cmd) {
+            self.hub.do_send(server::KickPlayer {
+                id,
+                room,
+                target: kick.id,
+                reason: kick.reason,
+                ban,
+            });
+        }
+    }
+
+    /// ask the server to move the room to another state
+    fn change_state(&self, ctx: &mut ws::WebsocketContext<Self>, cmd: &str, state: RoomState) {
+        if let Some((id, room)) = self.membership(ctx, cmd) {
+            self.hub.do_send(server::ChangeState { id, room, state });
+        }
+    }
+
+    /// the player's ID and room code. Tells the client it is not in a room when
+    /// the request with the given `cmd` requires one
+    fn membership(
+        &self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        cmd: &str,
+    ) -> Option<(String, String)> {
+        if let (Some(id), Some(room)) = (&self.id, &self.room) {
+            return Some((id.clone(), room.clone()));
+        }
+        self.send(
+            ctx,
+            &Response::error(
+                ErrorCode::NotInRoom,
+                Some(cmd),
+                "join or create a room first",
+            ),
+        );
+        None
+    }
+
+    /// forward a signaling message to another member of the player's room, or
+    /// to all of them when no target is given. The message is built from the
+    /// player's ID
+    fn signal(
+        &self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        cmd: &'static str,
+        to: Option<String>,
+        msg: impl FnOnce(&str) -> Response,
+    ) {
+        let Some((id, room)) = self.membership(ctx, cmd) else {
+            return;
+        };
+        let msg = msg(&id);
+        match to {
+            Some(to) => self.hub.do_send(ToPlayer {
+                msg,
+                room,
+                id,
+                to,
+                cmd,
+            }),
+            None => self.hub.do_send(ToRoom { msg, room, id }),
+        }
+    }
+
+    /// serialize a response and send it down the websocket
+    fn send(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &Response) {
+        if let Ok(str) = serde_json::to_string(msg) {
+            ctx.text(str);
+        }
+    }
+
+    /// count a request against the rate limit, returning whether it may be handled
+    fn allow_request(&mut self) -> bool {
+        self.roll_window();
+        self.window_requests += 1;
+        self.window_requests <= self.request_limit
+    }
+
+    /// count a signaling request against its own rate limit, returning whether
+    /// it may be handled
+    fn allow_signaling(&mut self) -> bool {
+        self.roll_window();
+        self.window_signaling += 1;
+        self.window_signaling <= self.signaling_limit
+    }
+
+    /// count a binary frame against the rate limit, returning whether it may be relayed
+    fn allow_frame(&mut self) -> bool {
+        self.roll_window();
+        self.window_frames += 1;
+        self.window_frames <= RATE_LIMIT_FRAMES
+    }
+
+    /// start a new rate limiting window once the current one is over
+    fn roll_window(&mut self) {
+        if self.window_start.elapsed() >= RATE_LIMIT_WINDOW {
+            self.window_start = Instant::now();
+            self.window_requests = 0;
+            self.window_signaling = 0;
+            self.window_frames = 0;
         }
     }
 
@@ -201,8 +518,10 @@ impl PlayerSession {
 
                 // notify chat server
                 act.hub.do_send(server::Disconnect {
+                    addr: ctx.address(),
                     id: act.id.clone(),
                     room: act.room.clone(),
+                    reason: LeaveReason::Timeout,
                 });
 
                 // stop actor
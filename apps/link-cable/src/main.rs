@@ -10,17 +10,36 @@ use actix_web_actors::ws;
 mod libs;
 use libs::{
     api,
-    socket::{server, session::PlayerSession},
+    config::RoomConfig,
+    socket::{matchmaker::Matchmaker, server, session::PlayerSession},
 };
-use tokio;
 
 /// Entry point for our route
 async fn socket_route(
     req: HttpRequest,
     stream: web::Payload,
     srv: web::Data<Addr<server::RoomServer>>,
+    matchmaker: web::Data<Addr<Matchmaker>>,
+    config: web::Data<RoomConfig>,
 ) -> Result<impl Responder, Error> {
-    ws::start(PlayerSession::new(srv.get_ref().clone()), &req, stream)
+    // forwarding headers are set by the client unless a trusted proxy replaces them
+    let ip = if config.trust_proxy {
+        req.connection_info()
+            .realip_remote_addr()
+            .map(str::to_owned)
+    } else {
+        req.peer_addr().map(|addr| addr.ip().to_string())
+    };
+    ws::start(
+        PlayerSession::new(
+            srv.get_ref().clone(),
+            matchmaker.get_ref().clone(),
+            ip,
+            config.get_ref(),
+        ),
+        &req,
+        stream,
+    )
 }
 
 async fn default_service(req: HttpRequest) -> impl Responder {
@@ -40,7 +59,10 @@ async fn main() -> std::io::Result<()> {
     let bind_address = env::var("BIND_ADDRESS").unwrap_or_else(|_| String::from("localhost"));
     log::info!("Binding to address: {}:8080", bind_address);
     // start room server actor
-    let server = server::RoomServer::default().start();
+    let config = RoomConfig::from_env();
+    let server = server::RoomServer::new(config.clone()).start();
+    // start the matchmaking queue alongside it
+    let matchmaker = Matchmaker::new(server.clone(), config.clone()).start();
 
     let api_server = {
         let server = server.clone();
@@ -61,6 +83,8 @@ async fn main() -> std::io::Result<()> {
         HttpServer::new(move || {
             App::new()
                 .app_data(web::Data::new(server.clone()))
+                .app_data(web::Data::new(matchmaker.clone()))
+                .app_data(web::Data::new(config.clone()))
                 .route("/{code:.*}", web::get().to(socket_route)) // WebSocket route
                 .wrap(Logger::default())
         })
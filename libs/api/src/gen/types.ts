@@ -4,15 +4,222 @@
 
 export interface Player {
   id: string;
+  c?: number;
   i?: number;
+  /** whether the player is ready to start the match */
+  ready: boolean;
+  /** whether the player is watching the match rather than playing in it */
+  spectator: boolean;
+}
+
+/** the player's own info, sent only to them */
+export interface You {
+  id: string;
   c?: number;
+  i?: number;
+  /** secret used to resume the session after losing connection */
+  token: string;
+  /** whether the player is watching the match rather than playing in it */
+  spectator: boolean;
+}
+
+/** a player and their seat, for events about that player */
+export interface PlayerStatus {
+  id: string;
+  i?: number;
+}
+
+/** which side of a WebRTC connection a player is on */
+export enum PeerRole {
+  /** creates the offer */
+  Offerer = 'offerer',
+  /** waits for the offer and answers it */
+  Answerer = 'answerer',
+}
+
+/** an instruction to open a WebRTC connection with another player */
+export interface ConnectTo {
+  peer_id: string;
+  role: PeerRole;
+}
+
+/** how a match is won */
+export enum GameMode {
+  /** the last player with lives left wins */
+  Stock = 'stock',
+  /** the player with the most knockouts when time runs out wins */
+  Time = 'time',
+}
+
+/** how the players of a room connect to each other over WebRTC */
+export enum Topology {
+  /** every player connects to every other player */
+  Mesh = 'mesh',
+  /** every player connects to the host only */
+  Star = 'star',
+}
+
+/**
+ * per-room settings chosen by the host, shared with every player so that
+ * all clients generate the same world
+ */
+export interface RoomSettings {
+  /** seed for generating the map. A random one is picked when absent */
+  seed: number;
+  mode?: GameMode;
+  /** length of a match in seconds. Required in `Time` mode */
+  time_limit?: number;
+  /** whether the room is listed for anyone to join, rather than only by code */
+  public?: boolean;
+  /** whether players play in teams */
+  teams?: boolean;
+  /**
+   * how players connect to each other. Changing it only affects
+   * connections planned afterwards
+   */
+  topology?: Topology;
+}
+
+/** a STUN or TURN server, in the shape of `RTCIceServer` */
+export interface IceServer {
+  urls: string[];
+  username?: string;
+  credential?: string;
+}
+
+/** the ICE servers the client should use for its WebRTC connections */
+export interface IceConfig {
+  ice_servers: IceServer[];
+}
+
+/** whether the player is waiting in the matchmaking queue */
+export interface QueueStatus {
+  queued: boolean;
+}
+
+/** the stage of a room's lifecycle */
+export enum RoomState {
+  /** players are gathering and getting ready */
+  Lobby = 'lobby',
+  /** the match is about to start */
+  Countdown = 'countdown',
+  /** the match is being played */
+  InMatch = 'in_match',
+  /** the match is over */
+  PostMatch = 'post_match',
+}
+
+export interface StateInfo {
+  state: RoomState;
+  /** seconds until the match starts, during the countdown */
+  countdown?: number;
+}
+
+export interface Ready {
+  id: string;
+  ready: boolean;
 }
 
 export interface RoomInfo {
   code: string;
   players: Player[];
+  /** the ID of the room's host */
+  host?: string;
+  state: RoomState;
+  settings: RoomSettings;
+  /** players watching the room without a seat */
+  spectators: Player[];
+  /** whether spectators take part in the WebRTC mesh */
+  spectator_mesh: boolean;
+}
+
+/** why a player is no longer in a room */
+export enum LeaveReason {
+  /** the player left or closed their connection */
+  Left = 'left',
+  /** the player's connection stopped responding */
+  Timeout = 'timeout',
+  /** the player was removed by the host */
+  Kicked = 'kicked',
+  /** the player was removed by the host, and may not come back */
+  Banned = 'banned',
+  /** the player's session was resumed from another connection */
+  Resumed = 'resumed',
+}
+
+export interface PlayerLeft {
+  id: string;
+  /** the seat the player was in */
+  i?: number;
+  reason: LeaveReason;
+  /** an explanation for the player who was removed */
+  message?: string;
+}
+
+/** a short overview of a room, as listed by `GET /rooms` */
+export interface RoomSummary {
+  code: string;
+  /** number of players currently in the room */
+  players: number;
+  /** number of seats still available */
+  open: number;
+  /** unix timestamp (seconds) of when the room was created */
+  created_at: number;
+  /** whether a match is currently being played in the room */
+  in_match: boolean;
 }
 
 export interface Chat {
   msg: string;
 }
+
+/** the reason a request could not be fulfilled */
+export enum ErrorCode {
+  /** no room exists with the requested code */
+  RoomNotFound = 'room_not_found',
+  /** the room has no free seats */
+  RoomFull = 'room_full',
+  /** the requested seat is taken or does not exist */
+  SeatUnavailable = 'seat_unavailable',
+  /** the resume token is unknown or has expired */
+  ResumeFailed = 'resume_failed',
+  /** the request can only be made by the room's host */
+  NotHost = 'not_host',
+  /**
+   * the player the request refers to is not in the room, or is spectating
+   * when the request needs a seated player
+   */
+  PlayerNotFound = 'player_not_found',
+  /** the player is banned from the room */
+  Banned = 'banned',
+  /** the player could not be banned because their address is unknown */
+  BanFailed = 'ban_failed',
+  /** the request is not allowed in the room's current state */
+  InvalidState = 'invalid_state',
+  /** not every player is ready to start the match */
+  NotReady = 'not_ready',
+  /** the chosen character is not in the roster */
+  InvalidCharacter = 'invalid_character',
+  /** another player in the room already chose the character */
+  CharacterTaken = 'character_taken',
+  /** the requested room settings are not valid */
+  InvalidSettings = 'invalid_settings',
+  /** the room's match has started and it does not take spectators */
+  MatchInProgress = 'match_in_progress',
+  /** a new room could not be created */
+  RoomCreationFailed = 'room_creation_failed',
+  /** the request is only valid while in a room */
+  NotInRoom = 'not_in_room',
+  /** the request could not be parsed */
+  MalformedRequest = 'malformed_request',
+  /** the client is sending requests too quickly */
+  RateLimited = 'rate_limited',
+}
+
+export interface ErrorInfo {
+  code: ErrorCode;
+  /** a human-readable description of the error */
+  message: string;
+  /** the `cmd` of the request that failed, if known */
+  request_cmd?: string;
+}
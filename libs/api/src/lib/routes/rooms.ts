@@ -1,5 +1,6 @@
 import { initContract } from '@ts-rest/core';
 import { z } from 'zod';
+import { RoomInfo, RoomSummary } from '../../gen/types';
 
 const c = initContract();
 
@@ -19,10 +20,25 @@ export const rooms = c.router(
     get: {
       method: 'GET',
       path: '/',
+      query: z.object({
+        public: z.boolean().optional(),
+      }),
       responses: {
-        200: c.type<void>(),
+        200: c.type<RoomSummary[]>(),
+      },
+      summary: 'List the rooms on the server, or only the public ones',
+    },
+    room: {
+      method: 'GET',
+      path: '/:code',
+      pathParams: z.object({
+        code: z.string(),
+      }),
+      responses: {
+        200: c.type<RoomInfo>(),
+        404: c.type<void>(),
       },
-      summary: 'Dummy endpoint to check api pathing',
+      summary: 'Get the players and seats of a single room',
     },
   },
   { pathPrefix: '/rooms' }